@@ -0,0 +1,136 @@
+use nalgebra_glm::Vec3;
+
+/// Glow around emissive surfaces: bright emissive pixels are thresholded, blurred on a
+/// chain of half-resolution images and the levels are added back up to full size.
+pub struct Bloom {
+  // Emissive brightness (max channel) below which nothing glows
+  pub threshold: f32,
+  // Scale of the glow added to the frame
+  pub intensity: f32,
+  // Number of mip levels; each one doubles the blur radius
+  pub levels: usize,
+}
+
+impl Default for Bloom {
+  fn default() -> Self {
+    Bloom { threshold: 0.6, intensity: 0.9, levels: 5 }
+  }
+}
+
+struct Image {
+  width: usize,
+  height: usize,
+  pixels: Vec<Vec3>,
+}
+
+impl Image {
+  fn get(&self, x: i32, y: i32) -> Vec3 {
+    let x = x.clamp(0, self.width as i32 - 1) as usize;
+    let y = y.clamp(0, self.height as i32 - 1) as usize;
+    self.pixels[y * self.width + x]
+  }
+
+  // Bilinear lookup, (u, v) in [0,1] across the image
+  fn sample(&self, u: f32, v: f32) -> Vec3 {
+    let fx = u * self.width as f32 - 0.5;
+    let fy = v * self.height as f32 - 0.5;
+    let (x0, y0) = (fx.floor() as i32, fy.floor() as i32);
+    let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+    let top = self.get(x0, y0) * (1.0 - tx) + self.get(x0 + 1, y0) * tx;
+    let bottom = self.get(x0, y0 + 1) * (1.0 - tx) + self.get(x0 + 1, y0 + 1) * tx;
+    top * (1.0 - ty) + bottom * ty
+  }
+
+  // 2x2 box filter to half resolution
+  fn downsample(&self) -> Image {
+    let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height as i32 {
+      for x in 0..width as i32 {
+        let sum = self.get(2 * x, 2 * y) + self.get(2 * x + 1, 2 * y) + self.get(2 * x, 2 * y + 1) + self.get(2 * x + 1, 2 * y + 1);
+        pixels.push(sum * 0.25);
+      }
+    }
+    Image { width, height, pixels }
+  }
+
+  // Separable 5-tap binomial blur (approximates a gaussian)
+  fn blur(&self) -> Image {
+    const WEIGHTS: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+    let pass = |image: &Image, dx: i32, dy: i32| {
+      let mut pixels = Vec::with_capacity(image.pixels.len());
+      for y in 0..image.height as i32 {
+        for x in 0..image.width as i32 {
+          let mut sum = Vec3::zeros();
+          for (i, weight) in WEIGHTS.iter().enumerate() {
+            let k = i as i32 - 2;
+            sum += image.get(x + k * dx, y + k * dy) * *weight;
+          }
+          pixels.push(sum);
+        }
+      }
+      Image { width: image.width, height: image.height, pixels }
+    };
+    pass(&pass(self, 1, 0), 0, 1)
+  }
+
+  // Adds `smaller`, upsampled bilinearly, onto this image
+  fn add_upsampled(&mut self, smaller: &Image) {
+    for y in 0..self.height {
+      for x in 0..self.width {
+        let u = (x as f32 + 0.5) / self.width as f32;
+        let v = (y as f32 + 0.5) / self.height as f32;
+        self.pixels[y * self.width + x] += smaller.sample(u, v);
+      }
+    }
+  }
+}
+
+impl Bloom {
+  /// Computes the glow for a `width` x `height` image of linear emissive color.
+  /// The result has the same size and is meant to be added to the frame.
+  pub fn glow(&self, emissive: &[Vec3], width: usize, height: usize) -> Vec<Vec3> {
+    // Keep only the part of each emissive pixel above the threshold
+    let bright = Image {
+      width,
+      height,
+      pixels: emissive
+        .iter()
+        .map(|color| {
+          let brightness = color.max();
+          if brightness <= self.threshold {
+            Vec3::zeros()
+          } else {
+            color * ((brightness - self.threshold) / brightness)
+          }
+        })
+        .collect(),
+    };
+
+    // Blurring ever smaller levels gives a wide glow for little work
+    let mut chain = vec![bright.downsample().blur()];
+    while chain.len() < self.levels.max(1) {
+      let last = chain.last().unwrap();
+      if last.width <= 2 || last.height <= 2 {
+        break;
+      }
+      chain.push(last.downsample().blur());
+    }
+    while chain.len() > 1 {
+      let smaller = chain.pop().unwrap();
+      chain.last_mut().unwrap().add_upsampled(&smaller);
+    }
+
+    let glow = &chain[0];
+    let scale = self.intensity / self.levels.max(1) as f32;
+    let mut output = Vec::with_capacity(width * height);
+    for y in 0..height {
+      for x in 0..width {
+        let u = (x as f32 + 0.5) / width as f32;
+        let v = (y as f32 + 0.5) / height as f32;
+        output.push(glow.sample(u, v) * scale);
+      }
+    }
+    output
+  }
+}
@@ -0,0 +1,45 @@
+use nalgebra_glm::{Vec3, Mat4};
+use nalgebra_glm as glm;
+use std::f32::consts::PI;
+
+pub struct Camera {
+  pub eye: Vec3,
+  pub target: Vec3,
+  pub up: Vec3,
+  pub fov: f32,
+  pub near: f32,
+  pub far: f32,
+}
+
+impl Camera {
+  pub fn new(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+    Camera {
+      eye,
+      target,
+      up,
+      fov: PI / 4.0,
+      near: 0.1,
+      far: 1000.0,
+    }
+  }
+
+  // World space -> camera space (right handed, camera looks down -Z)
+  pub fn view_matrix(&self) -> Mat4 {
+    glm::look_at(&self.eye, &self.target, &self.up)
+  }
+
+  // Camera space -> clip space. NDC depth ends up in [-1, 1], smaller is closer.
+  pub fn projection_matrix(&self, width: f32, height: f32) -> Mat4 {
+    glm::perspective(width / height, self.fov, self.near, self.far)
+  }
+}
+
+/// Maps NDC to screen pixels: x right, y down (minifb rows) and depth to [0, 1].
+pub fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
+  Mat4::new(
+    width / 2.0, 0.0,           0.0, width / 2.0,
+    0.0,         -height / 2.0, 0.0, height / 2.0,
+    0.0,         0.0,           0.5, 0.5,
+    0.0,         0.0,           0.0, 1.0,
+  )
+}
@@ -0,0 +1,94 @@
+use nalgebra_glm::{Vec4, Mat4};
+use crate::vertex::Vertex;
+use crate::shaders::clip_to_screen;
+
+// The six frustum planes in homogeneous clip space. A vertex is inside a plane
+// when its signed distance is >= 0 (e.g. -w <= x <= w for the left/right pair).
+const CLIP_PLANES: [fn(&Vec4) -> f32; 6] = [
+  |p| p.w + p.x, // left
+  |p| p.w - p.x, // right
+  |p| p.w + p.y, // bottom
+  |p| p.w - p.y, // top
+  |p| p.w + p.z, // near
+  |p| p.w - p.z, // far
+];
+
+/// Clips a triangle against the view frustum (Sutherland–Hodgman) and returns the
+/// resulting triangles. Every vertex attribute is interpolated along the clipped
+/// edges, and new vertices get their screen position from `viewport_matrix`.
+pub fn clip_triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, viewport_matrix: &Mat4) -> Vec<[Vertex; 3]> {
+  let vertices = [v1, v2, v3];
+
+  // Trivial accept / reject before doing any polygon work
+  let mut all_inside = true;
+  for plane in CLIP_PLANES.iter() {
+    let inside = vertices.iter().filter(|v| plane(&v.clip_position) >= 0.0).count();
+    if inside == 0 {
+      return Vec::new();
+    }
+    if inside < 3 {
+      all_inside = false;
+    }
+  }
+  if all_inside {
+    return vec![[v1.clone(), v2.clone(), v3.clone()]];
+  }
+
+  let mut polygon: Vec<Vertex> = vertices.iter().map(|v| (*v).clone()).collect();
+  for plane in CLIP_PLANES.iter() {
+    polygon = clip_polygon(&polygon, plane);
+    if polygon.len() < 3 {
+      return Vec::new();
+    }
+  }
+
+  for vertex in polygon.iter_mut() {
+    vertex.transformed_position = clip_to_screen(&vertex.clip_position, viewport_matrix);
+  }
+
+  // Fan triangulation keeps the original winding order
+  let mut triangles = Vec::with_capacity(polygon.len() - 2);
+  for i in 1..polygon.len() - 1 {
+    triangles.push([polygon[0].clone(), polygon[i].clone(), polygon[i + 1].clone()]);
+  }
+  triangles
+}
+
+/// Clips a line segment against the near plane, so a segment passing behind the camera
+/// still draws the part in front of it. The screen edges are left to `line()`.
+pub fn clip_line(start: &Vertex, end: &Vertex, viewport_matrix: &Mat4) -> Option<[Vertex; 2]> {
+  let near = CLIP_PLANES[4];
+  let (d_start, d_end) = (near(&start.clip_position), near(&end.clip_position));
+  if d_start < 0.0 && d_end < 0.0 {
+    return None;
+  }
+  let mut segment = [start.clone(), end.clone()];
+  if (d_start >= 0.0) != (d_end >= 0.0) {
+    let outside = if d_start < 0.0 { 0 } else { 1 };
+    segment[outside] = start.lerp(end, d_start / (d_start - d_end));
+    segment[outside].transformed_position = clip_to_screen(&segment[outside].clip_position, viewport_matrix);
+  }
+  Some(segment)
+}
+
+fn clip_polygon(polygon: &[Vertex], plane: &fn(&Vec4) -> f32) -> Vec<Vertex> {
+  let mut output = Vec::with_capacity(polygon.len() + 1);
+
+  for i in 0..polygon.len() {
+    let current = &polygon[i];
+    let next = &polygon[(i + 1) % polygon.len()];
+    let d_current = plane(&current.clip_position);
+    let d_next = plane(&next.clip_position);
+
+    if d_current >= 0.0 {
+      output.push(current.clone());
+    }
+    // Edge crosses the plane: emit the intersection point
+    if (d_current >= 0.0) != (d_next >= 0.0) {
+      let t = d_current / (d_current - d_next);
+      output.push(current.lerp(next, t));
+    }
+  }
+
+  output
+}
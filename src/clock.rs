@@ -0,0 +1,56 @@
+// Longest frame the simulation accepts; a stall (dragging the window) shouldn't make
+// the bodies jump
+const MAX_FRAME_TIME: f64 = 0.1;
+const MAX_SPEED: f64 = 256.0;
+
+/// Simulation time, decoupled from the wall clock so it can be paused, sped up or run
+/// backwards.
+pub struct Clock {
+  time: f64,
+  // Simulation seconds per real second; negative runs time backwards
+  pub speed: f64,
+  pub paused: bool,
+}
+
+impl Default for Clock {
+  fn default() -> Self {
+    Clock { time: 0.0, speed: 1.0, paused: false }
+  }
+}
+
+impl Clock {
+  pub fn time(&self) -> f64 {
+    self.time
+  }
+
+  /// Moves the clock forward by `real_seconds` of wall time and returns how much
+  /// simulation time passed (zero while paused, negative in reverse).
+  pub fn advance(&mut self, real_seconds: f64) -> f64 {
+    if self.paused {
+      return 0.0;
+    }
+    let step = real_seconds.min(MAX_FRAME_TIME) * self.speed;
+    self.time += step;
+    step
+  }
+
+  pub fn faster(&mut self) {
+    self.speed = (self.speed * 2.0).clamp(-MAX_SPEED, MAX_SPEED);
+  }
+
+  pub fn slower(&mut self) {
+    let slower = self.speed / 2.0;
+    // Keep the direction, never stop completely (that's what pause is for)
+    if slower.abs() >= 1.0 / MAX_SPEED {
+      self.speed = slower;
+    }
+  }
+
+  pub fn reverse(&mut self) {
+    self.speed = -self.speed;
+  }
+
+  pub fn toggle_pause(&mut self) {
+    self.paused = !self.paused;
+  }
+}
@@ -1,4 +1,53 @@
 use std::fmt;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use nalgebra_glm::Vec3;
+
+static GAMMA_CORRECT: AtomicBool = AtomicBool::new(true);
+
+// With gamma correction, colors authored in sRGB are decoded to linear before any
+// lighting math and the final color is encoded back to sRGB for display. Turning it
+// off brings back the old look, where the math ran directly on the sRGB values.
+pub fn set_gamma_correct(enabled: bool) {
+  GAMMA_CORRECT.store(enabled, Ordering::Relaxed);
+}
+
+pub fn get_gamma_correct() -> bool {
+  GAMMA_CORRECT.load(Ordering::Relaxed)
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+  if c <= 0.04045 {
+    c / 12.92
+  } else {
+    ((c + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+  if c <= 0.0031308 {
+    c * 12.92
+  } else {
+    1.055 * c.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+/// A color authored in sRGB (palette constants, textures), converted to the space
+/// the shaders light in.
+pub fn srgb(r: f32, g: f32, b: f32) -> Vec3 {
+  let color = Vec3::new(r, g, b);
+  if get_gamma_correct() {
+    color.map(srgb_to_linear)
+  } else {
+    color
+  }
+}
+
+// Every 8-bit sRGB value decoded to linear
+fn srgb_u8_table() -> &'static [f32; 256] {
+  static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+  TABLE.get_or_init(|| std::array::from_fn(|i| srgb_to_linear(i as f32 / 255.0)))
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -19,8 +68,63 @@ impl Color {
   }
 
 
+  // Linear blend towards another color, t in [0,1]
+  pub fn lerp(&self, other: &Color, t: f32) -> Self {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round().clamp(0.0, 255.0) as u8;
+    Color {
+      r: mix(self.r, other.r),
+      g: mix(self.g, other.g),
+      b: mix(self.b, other.b),
+    }
+  }
+
+  // Barycentric blend of three colors, weights summing to 1
+  pub fn interpolate(c1: &Color, c2: &Color, c3: &Color, w: (f32, f32, f32)) -> Self {
+    let mix = |a: u8, b: u8, c: u8| (a as f32 * w.0 + b as f32 * w.1 + c as f32 * w.2).round().clamp(0.0, 255.0) as u8;
+    Color {
+      r: mix(c1.r, c2.r, c3.r),
+      g: mix(c1.g, c2.g, c3.g),
+      b: mix(c1.b, c2.b, c3.b),
+    }
+  }
+
+  // Quantizes a float color in [0,1] to u8 channels (out of range values are clamped)
+  pub fn from_vec3(rgb: Vec3) -> Self {
+    // Rounded, so decoding a stored color and encoding it again gives it back unchanged
+    let channel = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+    Color::new(channel(rgb.x), channel(rgb.y), channel(rgb.z))
+  }
+
+  // Encodes a linear color for display (when gamma correction is on) and quantizes it
+  pub fn from_linear(rgb: Vec3) -> Self {
+    if get_gamma_correct() {
+      Color::from_vec3(rgb.map(|c| linear_to_srgb(c.clamp(0.0, 1.0))))
+    } else {
+      Color::from_vec3(rgb)
+    }
+  }
+
+  // Decodes a displayed 0xRRGGBB color back to linear, inverse of from_linear
+  pub fn hex_to_linear(hex: u32) -> Vec3 {
+    if get_gamma_correct() {
+      let table = srgb_u8_table();
+      Vec3::new(table[((hex >> 16) & 0xFF) as usize], table[((hex >> 8) & 0xFF) as usize], table[(hex & 0xFF) as usize])
+    } else {
+      Color::hex_to_vec3(hex)
+    }
+  }
+
+  // Inverse of to_hex, channels in [0,1]
+  pub fn hex_to_vec3(hex: u32) -> Vec3 {
+    Vec3::new(
+      ((hex >> 16) & 0xFF) as f32 / 255.0,
+      ((hex >> 8) & 0xFF) as f32 / 255.0,
+      (hex & 0xFF) as f32 / 255.0,
+    )
+  }
+
   // Function to return the color as a hex value
-  pub fn to_hex(&self) -> u32 {
+  pub fn to_hex(self) -> u32 {
     ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
   }
 }
@@ -61,3 +165,18 @@ impl fmt::Display for Color {
     write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn stored_colors_survive_a_round_trip_through_linear() {
+    for v in 0..=255u32 {
+      let hex = (v << 16) | (v << 8) | v;
+      // Gamma correction is on by default
+      assert_eq!(Color::from_linear(Color::hex_to_linear(hex)).to_hex(), hex, "grey level {v}");
+      assert_eq!(Color::from_vec3(Color::hex_to_vec3(hex)).to_hex(), hex, "grey level {v} without gamma");
+    }
+  }
+}
@@ -1,17 +1,23 @@
-use nalgebra_glm::Vec2;
-use crate::color::Color;
+use nalgebra_glm::{Vec2, Vec3};
 
 pub struct Fragment {
     pub position: Vec2,
-    pub color: Color,
+    // Linear RGB straight from the shader; may exceed 1 when rendering HDR
+    pub color: Vec3,
+    // Self-emitted part of the color, picked up by the bloom
+    pub emissive: Vec3,
+    // Coverage / opacity in [0,1]; 1 for solid fragments
+    pub alpha: f32,
     pub depth: f32,
 }
 
 impl Fragment {
-    pub fn new(x: f32, y: f32, color: Color, depth: f32) -> Self {
+    pub fn new(x: f32, y: f32, color: Vec3, depth: f32) -> Self {
         Fragment {
             position: Vec2::new(x, y),
             color,
+            emissive: Vec3::zeros(),
+            alpha: 1.0,
             depth,
         }
     }
@@ -1,10 +1,36 @@
 // framebuffer.rs
 
+use nalgebra_glm::Vec3;
+use crate::bloom::Bloom;
+use crate::color::{Color, get_gamma_correct};
+use crate::fragment::Fragment;
+use crate::heatmap::{Heatmap, heat_color};
+use crate::tonemap::ToneMap;
+use crate::triangle::FragmentSink;
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
+    // Resolved 0xRRGGBB pixels, one per pixel
     pub buffer: Vec<u32>,
+    // One depth per sample: index (y * width + x) * samples + sample
     pub zbuffer: Vec<f32>,
+    // Per-sample colors when multisampling (empty with a single sample)
+    sample_buffer: Vec<u32>,
+    // Linear float color per sample when rendering HDR (empty otherwise)
+    hdr_buffer: Vec<Vec3>,
+    // Linear emissive color per sample while bloom is on (empty otherwise)
+    emissive_buffer: Vec<Vec3>,
+    samples: usize,
+    hdr: bool,
+    pub tone_map: ToneMap,
+    pub exposure: f32,
+    bloom_enabled: bool,
+    pub bloom: Bloom,
+    heatmap: Heatmap,
+    // Per-pixel fragment and shader invocation counts while a heatmap is shown
+    fragment_counts: Vec<u32>,
+    shader_counts: Vec<u32>,
     background_color: u32,
     current_color: u32,
 }
@@ -16,6 +42,18 @@ impl Framebuffer {
             height,
             buffer: vec![0; width * height],
             zbuffer: vec![f32::INFINITY; width * height],
+            sample_buffer: Vec::new(),
+            hdr_buffer: Vec::new(),
+            emissive_buffer: Vec::new(),
+            samples: 1,
+            hdr: false,
+            tone_map: ToneMap::Aces,
+            exposure: 1.0,
+            bloom_enabled: false,
+            bloom: Bloom::default(),
+            heatmap: Heatmap::Off,
+            fragment_counts: Vec::new(),
+            shader_counts: Vec::new(),
             background_color: 0x000000,
             current_color: 0xFFFFFF,
         }
@@ -25,17 +63,64 @@ impl Framebuffer {
         for pixel in self.buffer.iter_mut() {
             *pixel = self.background_color;
         }
+        for sample in self.sample_buffer.iter_mut() {
+            *sample = self.background_color;
+        }
+        let background = Color::hex_to_linear(self.background_color);
+        for sample in self.hdr_buffer.iter_mut() {
+            *sample = background;
+        }
+        for sample in self.emissive_buffer.iter_mut() {
+            *sample = Vec3::zeros();
+        }
+        self.fragment_counts.fill(0);
+        self.shader_counts.fill(0);
         for depth in self.zbuffer.iter_mut() {
             *depth = f32::INFINITY;
         }
     }
 
-    pub fn point(&mut self, x: usize, y: usize, depth: f32) {
-        if x < self.width && y < self.height {
-            let index = y * self.width + x;
-            if self.zbuffer[index] > depth {
-                self.buffer[index] = self.current_color;
-                self.zbuffer[index] = depth;
+    /// Draws the current color at a pixel if it passes the depth test. A `coverage`
+    /// below 1 blends it over what is there (anti-aliased lines) without writing depth.
+    pub fn point(&mut self, x: usize, y: usize, depth: f32, coverage: f32) {
+        let color = Color::hex_to_linear(self.current_color);
+        self.blend(x, y, depth, color, coverage);
+    }
+
+    /// Like `point`, but with the fragment's own color and alpha.
+    pub fn draw_fragment(&mut self, fragment: &Fragment) {
+        let (x, y) = (fragment.position.x as usize, fragment.position.y as usize);
+        self.blend(x, y, fragment.depth, fragment.color, fragment.alpha);
+    }
+
+    fn blend(&mut self, x: usize, y: usize, depth: f32, color: Vec3, coverage: f32) {
+        if x >= self.width || y >= self.height || coverage <= 0.0 {
+            return;
+        }
+        let index = y * self.width + x;
+        let opaque = coverage >= 1.0;
+        for sample in 0..self.samples {
+            let sample_index = index * self.samples + sample;
+            if self.zbuffer[sample_index] <= depth {
+                continue;
+            }
+            if self.hdr {
+                let target = &mut self.hdr_buffer[sample_index];
+                *target = target.lerp(&color, coverage.min(1.0));
+            } else {
+                let target = if self.samples == 1 {
+                    &mut self.buffer[index]
+                } else {
+                    &mut self.sample_buffer[sample_index]
+                };
+                *target = if opaque {
+                    Color::from_linear(color).to_hex()
+                } else {
+                    Color::from_linear(Color::hex_to_linear(*target).lerp(&color, coverage)).to_hex()
+                };
+            }
+            if opaque {
+                self.zbuffer[sample_index] = depth;
             }
         }
     }
@@ -47,4 +132,278 @@ impl Framebuffer {
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Switches the number of samples per pixel (1 disables MSAA). Sample storage is
+    /// reallocated and cleared, so call it between frames.
+    pub fn set_samples(&mut self, samples: usize) {
+        self.samples = samples.max(1);
+        self.zbuffer = vec![f32::INFINITY; self.width * self.height * self.samples];
+        self.allocate_color_samples();
+    }
+
+    pub fn hdr(&self) -> bool {
+        self.hdr
+    }
+
+    /// Renders into a linear float buffer instead of 8-bit colors, so shaders can
+    /// return values above 1. `resolve` tone maps it into `buffer`.
+    pub fn set_hdr(&mut self, hdr: bool) {
+        self.hdr = hdr;
+        self.allocate_color_samples();
+    }
+
+    pub fn bloom_enabled(&self) -> bool {
+        self.bloom_enabled
+    }
+
+    /// Records the emissive color of every sample and adds its glow in `resolve`.
+    pub fn set_bloom(&mut self, enabled: bool) {
+        self.bloom_enabled = enabled;
+        self.allocate_color_samples();
+    }
+
+    pub fn heatmap(&self) -> Heatmap {
+        self.heatmap
+    }
+
+    /// Counts fragments and shader invocations per pixel; `resolve` then shows the
+    /// chosen count as a false-color image instead of the frame.
+    pub fn set_heatmap(&mut self, heatmap: Heatmap) {
+        self.heatmap = heatmap;
+        let pixels = if heatmap == Heatmap::Off { 0 } else { self.width * self.height };
+        self.fragment_counts = vec![0; pixels];
+        self.shader_counts = vec![0; pixels];
+    }
+
+    fn allocate_color_samples(&mut self) {
+        let sample_count = self.width * self.height * self.samples;
+        self.sample_buffer = if self.samples > 1 && !self.hdr {
+            vec![self.background_color; sample_count]
+        } else {
+            Vec::new()
+        };
+        self.hdr_buffer = if self.hdr {
+            vec![Color::hex_to_linear(self.background_color); sample_count]
+        } else {
+            Vec::new()
+        };
+        self.emissive_buffer = if self.bloom_enabled {
+            vec![Vec3::zeros(); sample_count]
+        } else {
+            Vec::new()
+        };
+    }
+
+    fn average_samples(samples: &[Vec3]) -> Vec3 {
+        samples.iter().fold(Vec3::zeros(), |sum, color| sum + color) / samples.len() as f32
+    }
+
+    /// Produces the final 0xRRGGBB `buffer`: averages the samples of every pixel, adds
+    /// the bloom and, when rendering HDR, tone maps the result as the very last step.
+    pub fn resolve(&mut self) {
+        let counts = match self.heatmap {
+            Heatmap::Off => None,
+            Heatmap::Overdraw => Some(&self.fragment_counts),
+            Heatmap::ShaderCost => Some(&self.shader_counts),
+        };
+        if let Some(counts) = counts {
+            for (pixel, &count) in self.buffer.iter_mut().zip(counts) {
+                *pixel = heat_color(count);
+            }
+            return;
+        }
+
+        let glow = if self.bloom_enabled {
+            let emissive: Vec<Vec3> = self.emissive_buffer.chunks(self.samples).map(Self::average_samples).collect();
+            Some(self.bloom.glow(&emissive, self.width, self.height))
+        } else {
+            None
+        };
+
+        if self.hdr {
+            for (i, (pixel, colors)) in self.buffer.iter_mut().zip(self.hdr_buffer.chunks(self.samples)).enumerate() {
+                let mut color = Self::average_samples(colors);
+                if let Some(glow) = &glow {
+                    color += glow[i];
+                }
+                *pixel = Color::from_linear(self.tone_map.apply(color, self.exposure)).to_hex();
+            }
+            return;
+        }
+
+        if self.samples > 1 && get_gamma_correct() {
+            // Average in linear light so edges don't come out too dark
+            for (pixel, colors) in self.buffer.iter_mut().zip(self.sample_buffer.chunks(self.samples)) {
+                let sum = colors.iter().fold(Vec3::zeros(), |sum, &color| sum + Color::hex_to_linear(color));
+                *pixel = Color::from_linear(sum / self.samples as f32).to_hex();
+            }
+        } else if self.samples > 1 {
+            let samples = self.samples as u32;
+            for (pixel, colors) in self.buffer.iter_mut().zip(self.sample_buffer.chunks(self.samples)) {
+                let (mut r, mut g, mut b) = (0, 0, 0);
+                for color in colors {
+                    r += (color >> 16) & 0xFF;
+                    g += (color >> 8) & 0xFF;
+                    b += color & 0xFF;
+                }
+                // Round to nearest so a fully covered pixel resolves to its exact color
+                let average = |sum: u32| (sum + samples / 2) / samples;
+                *pixel = (average(r) << 16) | (average(g) << 8) | average(b);
+            }
+        }
+        // Without HDR the glow simply saturates on top of the 8-bit colors
+        if let Some(glow) = glow {
+            for (pixel, light) in self.buffer.iter_mut().zip(glow) {
+                *pixel = Color::from_linear(Color::hex_to_linear(*pixel) + light).to_hex();
+            }
+        }
+    }
+
+    /// Splits the framebuffer into horizontal bands of `rows` rows. Each band borrows
+    /// its own slice of every buffer, so bands can be written from different threads.
+    pub fn bands_mut(&mut self, rows: usize) -> Vec<FramebufferBand<'_>> {
+        let width = self.width;
+        let samples = self.samples;
+        let mut zbuffers = self.zbuffer.chunks_mut(width * rows * samples);
+        let mut sample_buffers = self.sample_buffer.chunks_mut(width * rows * samples);
+        let mut hdr_buffers = self.hdr_buffer.chunks_mut(width * rows * samples);
+        let mut emissive_buffers = self.emissive_buffer.chunks_mut(width * rows * samples);
+        let mut fragment_counts = self.fragment_counts.chunks_mut(width * rows);
+        let mut shader_counts = self.shader_counts.chunks_mut(width * rows);
+        self.buffer
+            .chunks_mut(width * rows)
+            .enumerate()
+            .map(|(i, buffer)| FramebufferBand {
+                y: i * rows,
+                width,
+                height: buffer.len() / width,
+                samples,
+                buffer,
+                zbuffer: zbuffers.next().unwrap(),
+                sample_buffer: sample_buffers.next().unwrap_or_default(),
+                hdr_buffer: hdr_buffers.next().unwrap_or_default(),
+                emissive_buffer: emissive_buffers.next().unwrap_or_default(),
+                fragment_counts: fragment_counts.next().unwrap_or_default(),
+                shader_counts: shader_counts.next().unwrap_or_default(),
+                pass: DepthPass::Single,
+            })
+            .collect()
+    }
+}
+
+/// How a band treats the depth test while triangles are rasterized into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DepthPass {
+    // Test against the depth written so far, shade when closer
+    Single,
+    // Only record the closest depth, never shade
+    PrePass,
+    // After a pre-pass: shade only the fragment that won the depth test
+    Shading,
+}
+
+/// A horizontal slice of the framebuffer. Fragments use framebuffer coordinates;
+/// anything outside the band is ignored.
+pub struct FramebufferBand<'a> {
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    samples: usize,
+    buffer: &'a mut [u32],
+    zbuffer: &'a mut [f32],
+    sample_buffer: &'a mut [u32],
+    hdr_buffer: &'a mut [Vec3],
+    emissive_buffer: &'a mut [Vec3],
+    fragment_counts: &'a mut [u32],
+    shader_counts: &'a mut [u32],
+    pub pass: DepthPass,
+}
+
+impl FramebufferBand<'_> {
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.width && y >= self.y && y < self.y + self.height {
+            Some((y - self.y) * self.width + x)
+        } else {
+            None
+        }
+    }
+}
+
+impl FragmentSink for FramebufferBand<'_> {
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+
+    fn depth_test(&mut self, x: usize, y: usize, sample: usize, depth: f32) -> bool {
+        let Some(index) = self.index(x, y) else {
+            return false;
+        };
+        let sample_index = index * self.samples + sample;
+        match self.pass {
+            DepthPass::Single => self.zbuffer[sample_index] > depth,
+            DepthPass::PrePass => {
+                if self.zbuffer[sample_index] > depth {
+                    self.zbuffer[sample_index] = depth;
+                }
+                false
+            }
+            DepthPass::Shading => self.zbuffer[sample_index] >= depth,
+        }
+    }
+
+    fn write(&mut self, fragment: &Fragment, sample: usize) {
+        let x = fragment.position.x as usize;
+        let y = fragment.position.y as usize;
+        if let Some(index) = self.index(x, y) {
+            let sample_index = index * self.samples + sample;
+            // Translucent fragments mix in linear space over what is already there and
+            // leave the depth alone, so whatever is behind them stays visible
+            let alpha = fragment.alpha.min(1.0);
+            let opaque = alpha >= 1.0;
+            let over = |target: Vec3, color: &Vec3| if opaque { *color } else { target.lerp(color, alpha) };
+            if !self.hdr_buffer.is_empty() {
+                self.hdr_buffer[sample_index] = over(self.hdr_buffer[sample_index], &fragment.color);
+            } else {
+                let target = if self.samples == 1 {
+                    &mut self.buffer[index]
+                } else {
+                    &mut self.sample_buffer[sample_index]
+                };
+                *target = if opaque {
+                    Color::from_linear(fragment.color).to_hex()
+                } else {
+                    Color::from_linear(Color::hex_to_linear(*target).lerp(&fragment.color, alpha)).to_hex()
+                };
+            }
+            if !self.emissive_buffer.is_empty() {
+                self.emissive_buffer[sample_index] = over(self.emissive_buffer[sample_index], &fragment.emissive);
+            }
+            if opaque {
+                self.zbuffer[sample_index] = fragment.depth;
+            }
+        }
+    }
+
+    fn count_fragment(&mut self, x: usize, y: usize) {
+        // The pre-pass rasterizes everything a second time; count the shading pass only
+        if self.pass == DepthPass::PrePass || self.fragment_counts.is_empty() {
+            return;
+        }
+        if let Some(index) = self.index(x, y) {
+            self.fragment_counts[index] += 1;
+        }
+    }
+
+    fn count_shader_invocation(&mut self, x: usize, y: usize) {
+        if self.shader_counts.is_empty() {
+            return;
+        }
+        if let Some(index) = self.index(x, y) {
+            self.shader_counts[index] += 1;
+        }
+    }
 }
@@ -0,0 +1,44 @@
+/// Diagnostic view that replaces the frame with a false-color count per pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Heatmap {
+  Off,
+  // Fragments generated by `triangle()` (covered pixels, hidden or not)
+  Overdraw,
+  // Times `shade()` ran
+  ShaderCost,
+}
+
+impl Heatmap {
+  pub fn next(self) -> Self {
+    match self {
+      Heatmap::Off => Heatmap::Overdraw,
+      Heatmap::Overdraw => Heatmap::ShaderCost,
+      Heatmap::ShaderCost => Heatmap::Off,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Heatmap::Off => "off",
+      Heatmap::Overdraw => "overdraw",
+      Heatmap::ShaderCost => "shader cost",
+    }
+  }
+}
+
+// 0, 1, 2, ... 7 and 8+ hits per pixel
+const RAMP: [u32; 9] = [
+  0x000000, // nothing drawn
+  0x102080, // dark blue
+  0x1060E0, // blue
+  0x10C0C0, // cyan
+  0x20C040, // green
+  0xE0E020, // yellow
+  0xF08020, // orange
+  0xE02020, // red
+  0xFFFFFF, // white
+];
+
+pub fn heat_color(count: u32) -> u32 {
+  RAMP[(count as usize).min(RAMP.len() - 1)]
+}
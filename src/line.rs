@@ -1,42 +1,127 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
-use crate::color::Color;
 
-pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
-    let mut fragments = Vec::new();
+/// How `line` rasterizes a segment.
+#[derive(Clone, Copy, Debug)]
+pub struct LineStyle {
+    // Width in pixels, measured across the line
+    pub thickness: f32,
+    // Xiaolin Wu style coverage: edge pixels get fractional alpha instead of stair steps
+    pub antialiased: bool,
+}
 
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle { thickness: 1.0, antialiased: false }
+    }
+}
+
+/// Rasterizes the segment between two screen-space vertices into fragments inside a
+/// `width` x `height` framebuffer. The segment is clipped to the framebuffer first, then
+/// stepped one pixel at a time along its major axis; depth and vertex color are
+/// interpolated along the segment and `alpha` carries the pixel coverage.
+pub fn line(a: &Vertex, b: &Vertex, style: &LineStyle, width: usize, height: usize) -> Vec<Fragment> {
+    let mut fragments = Vec::new();
     let start = a.transformed_position;
     let end = b.transformed_position;
 
-    let mut x0 = start.x as i32;
-    let mut y0 = start.y as i32;
-    let x1 = end.x as i32;
-    let y1 = end.y as i32;
+    // t0 and t1 are where the visible part begins and ends along a -> b
+    let Some((t0, t1)) = clip_to_rect(start.x, start.y, end.x, end.y, width as f32, height as f32) else {
+        return fragments;
+    };
 
-    let dx = (x1 - x0).abs();
-    let dy = (y1 - y0).abs();
+    // Work in (major, minor) axes so steep lines step along y
+    let steep = (end.y - start.y).abs() > (end.x - start.x).abs();
+    let axes = |p: &Vec3| if steep { (p.y, p.x) } else { (p.x, p.y) };
+    let (start_major, start_minor) = axes(&start);
+    let (end_major, end_minor) = axes(&end);
+    let (major_limit, minor_limit) = if steep { (height, width) } else { (width, height) };
 
-    let sx = if x0 < x1 { 1 } else { -1 };
-    let sy = if y0 < y1 { 1 } else { -1 };
+    let delta_major = end_major - start_major;
+    let delta_minor = end_minor - start_minor;
+    let gradient = if delta_major != 0.0 { delta_minor / delta_major } else { 0.0 };
+    // A diagonal line covers more minor-axis pixels for the same perpendicular thickness
+    let half_span = style.thickness.max(0.0) * (1.0 + gradient * gradient).sqrt() * 0.5;
 
-    let mut err = if dx > dy { dx / 2 } else { -dy / 2 };
+    let color_a = Color::hex_to_linear(a.color.to_hex());
+    let color_b = Color::hex_to_linear(b.color.to_hex());
 
-    loop {
-        let z = start.z + (end.z - start.z) * (x0 - start.x as i32) as f32 / (end.x - start.x) as f32;
-        fragments.push(Fragment::new(x0 as f32, y0 as f32, Color::new(255, 255, 255), z));
+    let visible_a = start_major + delta_major * t0;
+    let visible_b = start_major + delta_major * t1;
+    let first = visible_a.min(visible_b).floor() as i64;
+    let last = visible_a.max(visible_b).floor() as i64;
 
-        if x0 == x1 && y0 == y1 { break; }
+    for major in first.max(0)..=last.min(major_limit as i64 - 1) {
+        // Parameter along a -> b at this pixel's center, kept on the visible part
+        let center = major as f32 + 0.5;
+        let t = if delta_major != 0.0 {
+            ((center - start_major) / delta_major).clamp(t0, t1)
+        } else {
+            t0
+        };
+        let minor = start_minor + delta_minor * t;
+        let depth = start.z + (end.z - start.z) * t;
+        let color = color_a + (color_b - color_a) * t;
 
-        let e2 = err;
-        if e2 > -dx {
-            err -= dy;
-            x0 += sx;
-        }
-        if e2 < dy {
-            err += dx;
-            y0 += sy;
+        let mut emit = |minor_pixel: i64, alpha: f32| {
+            if minor_pixel < 0 || minor_pixel >= minor_limit as i64 || alpha <= 0.0 {
+                return;
+            }
+            let (x, y) = if steep { (minor_pixel, major) } else { (major, minor_pixel) };
+            let mut fragment = Fragment::new(x as f32, y as f32, color, depth);
+            fragment.alpha = alpha;
+            fragments.push(fragment);
+        };
+
+        let (low, high) = (minor - half_span, minor + half_span);
+        if style.antialiased {
+            // Each pixel gets the fraction of its extent the line's span overlaps
+            for pixel in low.floor() as i64..=high.floor() as i64 {
+                let overlap = high.min(pixel as f32 + 1.0) - low.max(pixel as f32);
+                emit(pixel, overlap.min(1.0));
+            }
+        } else {
+            // A whole number of pixels centered on the line; at least the one under it
+            let count = (high - low).round().max(1.0) as i64;
+            let from = (minor - count as f32 * 0.5).round() as i64;
+            for pixel in from..from + count {
+                emit(pixel, 1.0);
+            }
         }
     }
 
     fragments
 }
+
+// Liang–Barsky clip of a segment against [0, width) x [0, height). Returns the
+// parameter range of the part inside, or None when it misses the rectangle.
+fn clip_to_rect(x0: f32, y0: f32, x1: f32, y1: f32, width: f32, height: f32) -> Option<(f32, f32)> {
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    // Keep the far edges just inside so floor() lands on the last pixel
+    let (max_x, max_y) = (width - 1e-3, height - 1e-3);
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+
+    for (p, q) in [(-dx, x0), (dx, max_x - x0), (-dy, y0), (dy, max_y - y0)] {
+        if p == 0.0 {
+            // Parallel to this edge: either fully outside or no constraint
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let r = q / p;
+        if p < 0.0 {
+            t0 = t0.max(r);
+        } else {
+            t1 = t1.min(r);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+
+    Some((t0, t1))
+}
@@ -1,6 +1,6 @@
-use nalgebra_glm::{Vec3, Mat4};
-use minifb::{Key, Window, WindowOptions};
-use std::time::Duration;
+use nalgebra_glm::{Vec3, Mat3, Mat4};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+use std::time::{Duration, Instant};
 use std::f32::consts::PI;
 
 mod framebuffer;
@@ -11,16 +11,58 @@ mod obj;
 mod color;
 mod fragment;
 mod shaders;
+mod camera;
+mod clipping;
+mod tiles;
+mod stats;
+mod postprocess;
+mod tonemap;
+mod bloom;
+mod overlay;
+mod heatmap;
+mod scene;
+mod orbit;
+mod clock;
+mod nbody;
+mod paths;
 
 use framebuffer::Framebuffer;
-use vertex::Vertex;
 use obj::Obj;
-use triangle::triangle;
-use shaders::{vertex_shader, set_shader_index, set_noise_seed};
+use line::LineStyle;
+use overlay::NormalOverlay;
+use heatmap::Heatmap;
+use scene::{DrawItem, Scene, solar_system};
+use clock::Clock;
+use nbody::NBody;
+use paths::PathOverlay;
+use triangle::{is_culled, CullMode, Primitive, set_cull_mode, get_cull_mode, triangle_outline, RenderMode, set_render_mode, get_render_mode};
+use shaders::{vertex_shader, set_noise_seed, set_debug_view, get_debug_view, set_depth_range, DebugView, Material, set_material_override, get_material_override};
+use camera::{Camera, create_viewport_matrix};
+use clipping::clip_triangle;
+use tiles::{rasterize, worker_count, set_depth_prepass, get_depth_prepass};
+use stats::RenderStats;
+use color::{set_gamma_correct, get_gamma_correct};
+use postprocess::{PostChain, Fxaa, Vignette, FilmGrain, ChromaticAberration};
 
 
+#[derive(Clone, Copy)]
 pub struct Uniforms {
     model_matrix: Mat4,
+    normal_matrix: Mat3,
+    view_matrix: Mat4,
+    projection_matrix: Mat4,
+    viewport_matrix: Mat4,
+}
+
+impl Uniforms {
+    // Same camera, placed with another model matrix (one per scene node)
+    fn with_model(&self, model_matrix: Mat4) -> Uniforms {
+        Uniforms {
+            model_matrix,
+            normal_matrix: create_normal_matrix(&model_matrix),
+            ..*self
+        }
+    }
 }
 
 fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
@@ -61,42 +103,106 @@ fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
     transform_matrix * rotation_matrix
 }
 
-fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
-    // Vertex Shader Stage
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
-    }
+// Inverse transpose of the model matrix's upper 3x3, for transforming normals
+fn create_normal_matrix(model_matrix: &Mat4) -> Mat3 {
+    nalgebra_glm::mat4_to_mat3(model_matrix)
+        .try_inverse()
+        .map_or_else(Mat3::identity, |inverse| inverse.transpose())
+}
+
+// Pulls edges slightly towards the camera so they win the depth test against the
+// surface they lie on, while faces in front of them still hide them
+const WIREFRAME_DEPTH_BIAS: f32 = 1e-4;
 
-    // Primitive Assembly Stage
+// Fixed N-body step in simulation seconds; the fastest moon takes ~2000 steps per orbit
+const NBODY_TIMESTEP: f64 = 4e-3;
+
+fn render(framebuffer: &mut Framebuffer, frame: &Uniforms, scene: &Scene, wire_style: &LineStyle) -> RenderStats {
+    // Every node is transformed and clipped first so the whole scene is rasterized in
+    // one pass: tiles are binned once and the depth pre-pass sees every body
     let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+    let mut primitives = Vec::new();
+    let mut triangles_submitted = 0;
+    let mut triangles_culled = 0;
+
+    // Opaque nodes first; transparent ones last and back to front, so each one blends
+    // over everything behind it
+    let (mut items, mut transparent): (Vec<_>, Vec<_>) =
+        scene.draw_list().into_iter().partition(|item| !item.material.is_transparent());
+    let view_depth = |item: &DrawItem| (frame.view_matrix * item.model_matrix)[(2, 3)];
+    transparent.sort_by(|a, b| view_depth(a).total_cmp(&view_depth(b)));
+    items.extend(transparent);
+
+    for item in items {
+        let mesh = &scene.meshes[item.mesh];
+        let uniforms = frame.with_model(item.model_matrix);
+
+        // Vertex Shader Stage: each unique vertex is transformed once per frame
+        let mut transformed_vertices = Vec::with_capacity(mesh.vertices.len());
+        for vertex in &mesh.vertices {
+            let transformed = vertex_shader(vertex, &uniforms);
+            transformed_vertices.push(transformed);
+        }
+
+        // Transparent meshes carry a face for each side and must only ever draw the one
+        // facing the camera, or they would blend twice
+        let cull_mode = if item.material.is_transparent() { CullMode::Back } else { get_cull_mode() };
+
+        // Primitive Assembly + Clipping Stage
+        for tri in mesh.indices.chunks_exact(3) {
+            // Ids keep counting across nodes so every triangle in the frame is distinct
+            let primitive = Primitive { id: triangles_submitted as u32, material: item.material, sun: item.sun, rings: item.rings };
+            triangles_submitted += 1;
+            let clipped = clip_triangle(
+                &transformed_vertices[tri[0] as usize],
+                &transformed_vertices[tri[1] as usize],
+                &transformed_vertices[tri[2] as usize],
+                &uniforms.viewport_matrix,
+            );
+            let visible_before = triangles.len();
+            for clipped_tri in clipped.into_iter().filter(|tri| !is_culled(&tri[0], &tri[1], &tri[2], cull_mode)) {
+                triangles.push(clipped_tri);
+                primitives.push(primitive);
+            }
+            if triangles.len() == visible_before {
+                triangles_culled += 1;
+            }
         }
     }
 
-    // Rasterization Stage
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
+    // The linear depth view spreads over what is actually on screen
+    if get_debug_view() == DebugView::LinearDepth {
+        let (near, far) = triangles.iter().flatten().fold((f32::MAX, 0.0f32), |(near, far), vertex| {
+            (near.min(vertex.clip_position.w), far.max(vertex.clip_position.w))
+        });
+        set_depth_range(near, far);
+    }
+
+    // Rasterization + Fragment Processing Stage (tiled, multi-threaded)
+    let mode = get_render_mode();
+    let mut stats = RenderStats::default();
+    if mode != RenderMode::Wireframe {
+        stats = rasterize(framebuffer, &triangles, &primitives, worker_count());
     }
+    stats.triangles_submitted = triangles_submitted;
+    stats.triangles_culled = triangles_culled;
 
-    // Fragment Processing Stage
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
-        if x < framebuffer.width && y < framebuffer.height {
-            let color = fragment.color.to_hex();
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, fragment.depth);
+    // Wireframe: edges in the current color, depth-tested against the filled triangles
+    if mode != RenderMode::Solid {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        for tri in &triangles {
+            for fragment in triangle_outline(&tri[0], &tri[1], &tri[2], wire_style, width, height) {
+                framebuffer.point(
+                    fragment.position.x as usize,
+                    fragment.position.y as usize,
+                    fragment.depth - WIREFRAME_DEPTH_BIAS,
+                    fragment.alpha,
+                );
+            }
         }
     }
+
+    stats
 }
 
 fn main() {
@@ -120,6 +226,7 @@ fn main() {
 
     // Fondo negro para el render
     framebuffer.set_background_color(0x000000);
+    framebuffer.set_bloom(true);
 
     // Seed procedural randomness once per run
     if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
@@ -127,32 +234,210 @@ fn main() {
         set_noise_seed(nanos);
     }
 
-    // Initial transform: move the model to the center of the window and set
-    // a smaller scale so the camera appears a bit further from the object by default.
-    let mut translation = Vec3::new(300.0, 200.0, 0.0);
-    let mut rotation = Vec3::new(0.0, 0.0, 0.0);
-    // Reduced initial scale (was 100.0, previously set to 50.0 and then 25.0)
-    // to make the object appear even smaller / further away on start.
-    // Lower this value to move the camera further away from the object.
-    let mut scale = 4.5f32;
+    // Obj::load negates Y and Z, so world +Y points down the screen. Looking down +Z
+    // with -Y as "up" keeps the planets oriented (and lit) the way the shaders expect.
+    // Above the orbital plane (-Y is up) to see the whole system
+    let camera = Camera::new(
+        Vec3::new(0.0, -4.5, -9.0),
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+    );
 
+    // A sun, planets and moons, all instances of the same sphere model. The arrow keys,
+    // zoom and rotation keys move the scene's root node, so the whole system follows.
     let obj = Obj::load("assets/models/planetaff.obj").expect("Failed to load obj");
-    let vertex_arrays = obj.get_vertex_array(); 
+    let mut scene = solar_system(obj.get_indexed_mesh());
+
+    // Orbits and spins follow the simulation clock: P pauses, , / . slow down / speed up
+    // and / reverses time
+    let mut clock = Clock::default();
+    let mut last_frame = Instant::now();
+    // D switches to the N-body simulation, starting from where the scripted orbits are
+    // now, and back
+    let mut simulation: Option<NBody> = None;
+
+    // Orbit ellipses / predicted trajectories: Tab picks all bodies or one, F toggles its path
+    let mut path_overlay = PathOverlay::new(scene.body_info().len());
+
+    // Camera part of the uniforms; each node adds its own model matrix
+    let frame = Uniforms {
+        model_matrix: Mat4::identity(),
+        normal_matrix: Mat3::identity(),
+        view_matrix: camera.view_matrix(),
+        projection_matrix: camera.projection_matrix(framebuffer_width as f32, framebuffer_height as f32),
+        viewport_matrix: create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32),
+    };
+
+    // Post effects run in this order after the frame is resolved; F1-F4 toggle them
+    let mut post_chain = PostChain::new()
+        .with(Fxaa::default(), true)
+        .with(Vignette::default(), true)
+        .with(FilmGrain::default(), false)
+        .with(ChromaticAberration::default(), false);
+    let post_keys = [Key::F1, Key::F2, Key::F3, Key::F4];
+
+    let mut wire_style = LineStyle::default();
+    let mut normal_overlay = NormalOverlay::new(&scene.meshes);
 
     while window.is_open() {
         if window.is_key_down(Key::Escape) {
             break;
         }
 
-        handle_input(&window, &mut translation, &mut rotation, &mut scale);
+        let root = &mut scene.root;
+        handle_input(&window, &mut root.translation, &mut root.rotation, &mut root.scale);
 
-        framebuffer.clear();
+        // MSAA: press M to cycle 1x -> 2x -> 4x -> 8x samples per pixel
+        if window.is_key_pressed(Key::M, KeyRepeat::No) {
+            let samples = if framebuffer.samples() >= 8 { 1 } else { framebuffer.samples() * 2 };
+            framebuffer.set_samples(samples);
+        }
 
-        let model_matrix = create_model_matrix(translation, scale, rotation);
-        let uniforms = Uniforms { model_matrix };
+        // L switches between the gamma-correct pipeline and the old look
+        if window.is_key_pressed(Key::L, KeyRepeat::No) {
+            set_gamma_correct(!get_gamma_correct());
+        }
+
+        // Wireframe lines: K toggles anti-aliasing, [ / ] change the thickness
+        if window.is_key_pressed(Key::K, KeyRepeat::No) {
+            wire_style.antialiased = !wire_style.antialiased;
+        }
+        if window.is_key_pressed(Key::LeftBracket, KeyRepeat::No) {
+            wire_style.thickness = (wire_style.thickness - 0.5).max(0.5);
+        }
+        if window.is_key_pressed(Key::RightBracket, KeyRepeat::No) {
+            wire_style.thickness = (wire_style.thickness + 0.5).min(8.0);
+        }
+
+        // Normal overlay: N normals, B tangents, - / = length, 9 / 0 fewer / more vertices
+        if window.is_key_pressed(Key::N, KeyRepeat::No) {
+            normal_overlay.show_normals = !normal_overlay.show_normals;
+        }
+        if window.is_key_pressed(Key::B, KeyRepeat::No) {
+            normal_overlay.show_tangents = !normal_overlay.show_tangents;
+        }
+        if window.is_key_down(Key::Minus) {
+            normal_overlay.length = (normal_overlay.length / 1.03).max(0.01);
+        }
+        if window.is_key_down(Key::Equal) {
+            normal_overlay.length = (normal_overlay.length * 1.03).min(2.0);
+        }
+        if window.is_key_pressed(Key::Key9, KeyRepeat::No) {
+            normal_overlay.stride = (normal_overlay.stride * 2).min(64);
+        }
+        if window.is_key_pressed(Key::Key0, KeyRepeat::No) {
+            normal_overlay.stride = (normal_overlay.stride / 2).max(1);
+        }
+
+        // O cycles the heatmap: off -> fragments per pixel -> shader invocations per pixel
+        if window.is_key_pressed(Key::O, KeyRepeat::No) {
+            framebuffer.set_heatmap(framebuffer.heatmap().next());
+        }
+
+        // G toggles the bloom around emissive surfaces (the sun)
+        if window.is_key_pressed(Key::G, KeyRepeat::No) {
+            framebuffer.set_bloom(!framebuffer.bloom_enabled());
+        }
+
+        // HDR: H toggles the float buffer, J cycles the tone mapper, U / I change exposure
+        if window.is_key_pressed(Key::H, KeyRepeat::No) {
+            framebuffer.set_hdr(!framebuffer.hdr());
+        }
+        if window.is_key_pressed(Key::J, KeyRepeat::No) {
+            framebuffer.tone_map = framebuffer.tone_map.next();
+        }
+        if window.is_key_down(Key::U) {
+            framebuffer.exposure = (framebuffer.exposure / 1.02).max(0.05);
+        }
+        if window.is_key_down(Key::I) {
+            framebuffer.exposure = (framebuffer.exposure * 1.02).min(20.0);
+        }
+
+        for (i, key) in post_keys.iter().enumerate() {
+            if window.is_key_pressed(*key, KeyRepeat::No) {
+                post_chain.toggle(i);
+            }
+        }
+
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            clock.toggle_pause();
+        }
+        if window.is_key_pressed(Key::Comma, KeyRepeat::No) {
+            clock.slower();
+        }
+        if window.is_key_pressed(Key::Period, KeyRepeat::No) {
+            clock.faster();
+        }
+        if window.is_key_pressed(Key::Slash, KeyRepeat::No) {
+            clock.reverse();
+        }
+        if window.is_key_pressed(Key::D, KeyRepeat::No) {
+            simulation = match simulation {
+                Some(_) => None,
+                None => Some(NBody::new(scene.bodies(clock.time()), NBODY_TIMESTEP)),
+            };
+        }
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            path_overlay.select_next();
+        }
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            path_overlay.toggle();
+        }
+
+        let now = Instant::now();
+        let step = clock.advance((now - last_frame).as_secs_f64());
+        last_frame = now;
+        // Spins always follow the clock; in N-body mode the positions come from the simulation
+        scene.update(clock.time());
+        if let Some(simulation) = &mut simulation {
+            simulation.advance(step);
+            scene.set_body_positions(&simulation.bodies);
+        }
+
+        framebuffer.clear();
 
         framebuffer.set_current_color(0xFFDDDD);
-        render(&mut framebuffer, &uniforms, &vertex_arrays);
+        let stats = render(&mut framebuffer, &frame, &scene, &wire_style);
+        normal_overlay.draw(&mut framebuffer, &frame, &scene);
+        path_overlay.draw(&mut framebuffer, &frame, &scene, clock.time(), simulation.as_ref(), clock.speed);
+        window.set_title(&format!(
+            "Rust Graphics - Renderer Example | t {:.1}s x{}{}{} | paths: {} | MSAA {}x | {}{}{}{}{} | post: {} | triangles: {} culled: {} | fragments: {} shaded: {} early-z saved: {}{}{}",
+            clock.time(),
+            clock.speed,
+            if clock.paused { " (paused)" } else { "" },
+            match &simulation {
+                Some(simulation) => format!(" | n-body dE/E {:+.1e}", simulation.energy_drift()),
+                None => String::new(),
+            },
+            match path_overlay.selected {
+                Some(i) => format!("{} {}", scene.body_info()[i].name, if path_overlay.visible[i] { "on" } else { "off" }),
+                None => "all".to_string(),
+            },
+            framebuffer.samples(),
+            if framebuffer.hdr() {
+                format!("HDR {} x{:.2}", framebuffer.tone_map.name(), framebuffer.exposure)
+            } else {
+                "LDR".to_string()
+            },
+            if framebuffer.bloom_enabled() { " + bloom" } else { "" },
+            if get_gamma_correct() { " + sRGB" } else { "" },
+            if let Some(material) = get_material_override() { format!(" | all {}", material.name()) } else { String::new() },
+            if get_debug_view() != DebugView::Off { format!(" | debug: {}", get_debug_view().name()) } else { String::new() },
+            post_chain.enabled_names().join(", "),
+            stats.triangles_submitted,
+            stats.triangles_culled,
+            stats.fragments,
+            stats.shader_invocations,
+            stats.shader_invocations_saved(),
+            if get_depth_prepass() { " (depth pre-pass)" } else { "" },
+            if framebuffer.heatmap() != Heatmap::Off { format!(" | heatmap: {}", framebuffer.heatmap().name()) } else { String::new() },
+        ));
+
+        framebuffer.resolve();
+        // The heatmap colors are data; leave them untouched
+        if framebuffer.heatmap() == Heatmap::Off {
+            post_chain.apply(&mut framebuffer);
+        }
 
         window
             .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
@@ -164,16 +449,16 @@ fn main() {
 
 fn handle_input(window: &Window, translation: &mut Vec3, rotation: &mut Vec3, scale: &mut f32) {
     if window.is_key_down(Key::Right) {
-        translation.x += 10.0;
+        translation.x += 0.05;
     }
     if window.is_key_down(Key::Left) {
-        translation.x -= 10.0;
+        translation.x -= 0.05;
     }
     if window.is_key_down(Key::Up) {
-        translation.y -= 10.0;
+        translation.y -= 0.05;
     }
     if window.is_key_down(Key::Down) {
-        translation.y += 10.0;
+        translation.y += 0.05;
     }
     // Use multiplicative zoom so we can zoom out without hitting a hard lower bound.
     // This avoids crossing zero and gives a smooth, effectively unbounded zoom out.
@@ -201,14 +486,34 @@ fn handle_input(window: &Window, translation: &mut Vec3, rotation: &mut Vec3, sc
     if window.is_key_down(Key::Y) {
         rotation.z += PI / 10.0;
     }
-    // Shader selection: press 1, 2 or 3 to pick the shader variant
+    // Shader selection: 1, 2 or 3 draw every body as gas, rock or sun; 4 goes back to
+    // each node's own material
     if window.is_key_down(Key::Key1) {
-        set_shader_index(0);
+        set_material_override(Some(Material::Gas));
     }
     if window.is_key_down(Key::Key2) {
-        set_shader_index(1);
+        set_material_override(Some(Material::Rock));
     }
     if window.is_key_down(Key::Key3) {
-        set_shader_index(2);
+        set_material_override(Some(Material::Sun));
+    }
+    if window.is_key_down(Key::Key4) {
+        set_material_override(None);
+    }
+    // Depth pre-pass: press Z to toggle
+    if window.is_key_pressed(Key::Z, KeyRepeat::No) {
+        set_depth_prepass(!get_depth_prepass());
+    }
+    // Face culling: press C to cycle none -> back -> front
+    if window.is_key_pressed(Key::C, KeyRepeat::No) {
+        set_cull_mode(get_cull_mode().next());
+    }
+    // Debug views: press X to cycle normals, position, depth, barycentrics, UVs, triangle ids
+    if window.is_key_pressed(Key::X, KeyRepeat::No) {
+        set_debug_view(get_debug_view().next());
+    }
+    // Render mode: press V to cycle solid -> wireframe -> solid + wireframe
+    if window.is_key_pressed(Key::V, KeyRepeat::No) {
+        set_render_mode(get_render_mode().next());
     }
 }
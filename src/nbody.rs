@@ -0,0 +1,233 @@
+use nalgebra_glm::DVec3;
+use crate::orbit::GRAVITY;
+
+// Plummer softening length: keeps close passes finite. It is part of the potential as
+// well, so the softened system still conserves its energy.
+const SOFTENING: f64 = 1e-3;
+
+/// A point mass in scene space (world units, simulation seconds).
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+  pub mass: f64,
+  pub position: DVec3,
+  pub velocity: DVec3,
+}
+
+/// Gravitational N-body system integrated with velocity Verlet at a fixed timestep.
+/// The integrator is symplectic and time-reversible: energy error stays bounded
+/// instead of growing, and running the clock backwards retraces the motion.
+#[derive(Clone)]
+pub struct NBody {
+  pub bodies: Vec<Body>,
+  timestep: f64,
+  accelerations: Vec<DVec3>,
+  // Simulation time not yet covered by a whole step, carried to the next frame
+  pending: f64,
+  initial_energy: f64,
+  // Steps taken, minus steps taken backwards
+  steps: i64,
+}
+
+impl NBody {
+  /// Starts the simulation from `bodies`. Velocities are shifted so the total
+  /// momentum is zero, otherwise the whole system would drift off screen.
+  pub fn new(mut bodies: Vec<Body>, timestep: f64) -> Self {
+    let total_mass: f64 = bodies.iter().map(|body| body.mass).sum();
+    if total_mass > 0.0 {
+      let drift = momentum(&bodies) / total_mass;
+      for body in &mut bodies {
+        body.velocity -= drift;
+      }
+    }
+
+    let accelerations = accelerations(&bodies);
+    let initial_energy = energy(&bodies);
+    NBody { bodies, timestep, accelerations, pending: 0.0, initial_energy, steps: 0 }
+  }
+
+  /// Advances by `dt` simulation seconds in whole fixed steps; a negative `dt` steps
+  /// backwards. The remainder is kept for the next call so the motion doesn't depend
+  /// on the frame rate.
+  pub fn advance(&mut self, dt: f64) {
+    self.pending += dt;
+    while self.pending >= self.timestep {
+      self.step(self.timestep);
+      self.pending -= self.timestep;
+    }
+    while self.pending <= -self.timestep {
+      self.step(-self.timestep);
+      self.pending += self.timestep;
+    }
+  }
+
+  /// One kick-drift-kick velocity Verlet step of `dt`.
+  pub fn step(&mut self, dt: f64) {
+    for (body, acceleration) in self.bodies.iter_mut().zip(&self.accelerations) {
+      body.velocity += acceleration * (dt * 0.5);
+      body.position += body.velocity * dt;
+    }
+    self.accelerations = accelerations(&self.bodies);
+    for (body, acceleration) in self.bodies.iter_mut().zip(&self.accelerations) {
+      body.velocity += acceleration * (dt * 0.5);
+    }
+    self.steps += dt.signum() as i64;
+  }
+
+  /// Net number of steps taken so far; the state only changes when this does.
+  pub fn steps(&self) -> i64 {
+    self.steps
+  }
+
+  /// Where every body will be over the next `duration` simulation seconds (negative
+  /// looks into the past), sampled `samples` times at equal intervals. The simulation
+  /// itself is left untouched. Returns one path per body.
+  pub fn predict(&self, duration: f64, samples: usize) -> Vec<Vec<DVec3>> {
+    let mut future = self.clone();
+    future.pending = 0.0;
+    let mut paths: Vec<Vec<DVec3>> = self.bodies.iter().map(|body| vec![body.position]).collect();
+    for _ in 0..samples {
+      future.advance(duration / samples as f64);
+      for (path, body) in paths.iter_mut().zip(&future.bodies) {
+        path.push(body.position);
+      }
+    }
+    paths
+  }
+
+  /// Total kinetic plus potential energy.
+  pub fn energy(&self) -> f64 {
+    energy(&self.bodies)
+  }
+
+  /// Energy error relative to the start, (E - E0) / |E0|.
+  pub fn energy_drift(&self) -> f64 {
+    (self.energy() - self.initial_energy) / self.initial_energy.abs()
+  }
+}
+
+fn accelerations(bodies: &[Body]) -> Vec<DVec3> {
+  let mut accelerations = vec![DVec3::zeros(); bodies.len()];
+  // Each pair once; the force on j is the opposite of the force on i
+  for i in 0..bodies.len() {
+    for j in i + 1..bodies.len() {
+      let offset = bodies[j].position - bodies[i].position;
+      let distance_squared = offset.norm_squared() + SOFTENING * SOFTENING;
+      let pull = offset * (GRAVITY / (distance_squared * distance_squared.sqrt()));
+      accelerations[i] += pull * bodies[j].mass;
+      accelerations[j] -= pull * bodies[i].mass;
+    }
+  }
+  accelerations
+}
+
+fn energy(bodies: &[Body]) -> f64 {
+  let mut energy = 0.0;
+  for (i, body) in bodies.iter().enumerate() {
+    energy += 0.5 * body.mass * body.velocity.norm_squared();
+    for other in &bodies[i + 1..] {
+      let distance_squared = (other.position - body.position).norm_squared() + SOFTENING * SOFTENING;
+      energy -= GRAVITY * body.mass * other.mass / distance_squared.sqrt();
+    }
+  }
+  energy
+}
+
+fn momentum(bodies: &[Body]) -> DVec3 {
+  bodies.iter().map(|body| body.velocity * body.mass).sum()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::f64::consts::TAU;
+  use crate::obj::IndexedMesh;
+  use crate::scene::solar_system;
+
+  // A light planet on a Keplerian orbit with the given eccentricity around a unit mass,
+  // starting at periapsis. Returns the system and its orbital period.
+  fn sun_and_planet(eccentricity: f64, timestep: f64) -> (NBody, f64) {
+    let (sun_mass, planet_mass, semi_major_axis) = (1.0, 1e-3, 1.0);
+    let mu = GRAVITY * (sun_mass + planet_mass);
+    let periapsis = semi_major_axis * (1.0 - eccentricity);
+    // Vis-viva at periapsis
+    let speed = (mu * (2.0 / periapsis - 1.0 / semi_major_axis)).sqrt();
+    let bodies = vec![
+      Body { mass: sun_mass, position: DVec3::zeros(), velocity: DVec3::zeros() },
+      Body { mass: planet_mass, position: DVec3::new(periapsis, 0.0, 0.0), velocity: DVec3::new(0.0, 0.0, speed) },
+    ];
+    let period = TAU * (semi_major_axis.powi(3) / mu).sqrt();
+    (NBody::new(bodies, timestep), period)
+  }
+
+  fn angular_momentum(system: &NBody) -> DVec3 {
+    system.bodies.iter().map(|body| body.position.cross(&body.velocity) * body.mass).sum()
+  }
+
+  // Largest |energy drift| seen while running `orbits` periods, checked every step
+  fn max_drift(system: &mut NBody, period: f64, orbits: usize) -> f64 {
+    let steps = (period * orbits as f64 / system.timestep).round() as usize;
+    let mut worst = 0.0f64;
+    for _ in 0..steps {
+      system.step(system.timestep);
+      worst = worst.max(system.energy_drift().abs());
+    }
+    worst
+  }
+
+  #[test]
+  fn circular_orbit_conserves_energy_and_angular_momentum() {
+    let (mut system, period) = sun_and_planet(0.0, 1e-2);
+    let start_angular_momentum = angular_momentum(&system);
+
+    let worst = max_drift(&mut system, period, 200);
+
+    assert!(worst < 1e-6, "energy drift {worst:e} over 200 orbits");
+    let angular_error = (angular_momentum(&system) - start_angular_momentum).norm() / start_angular_momentum.norm();
+    assert!(angular_error < 1e-9, "angular momentum error {angular_error:e}");
+    assert!(momentum(&system.bodies).norm() < 1e-12);
+  }
+
+  #[test]
+  fn eccentric_orbit_energy_error_does_not_grow() {
+    let (mut system, period) = sun_and_planet(0.6, 2e-3);
+
+    // A symplectic integrator oscillates around the true energy; a non-symplectic one
+    // (e.g. explicit Euler) would keep drifting and fail the second bound
+    let early = max_drift(&mut system, period, 10);
+    for _ in 0..9 {
+      max_drift(&mut system, period, 10);
+    }
+    let late = max_drift(&mut system, period, 10);
+
+    assert!(early < 1e-4, "energy drift {early:e} in the first orbits");
+    assert!(late < early * 1.5, "energy drift grew from {early:e} to {late:e} over 100 orbits");
+  }
+
+  #[test]
+  fn solar_system_conserves_energy_over_a_long_run() {
+    let mesh = IndexedMesh { vertices: Vec::new(), indices: Vec::new() };
+    let scene = solar_system(mesh);
+    let mut system = NBody::new(scene.bodies(0.0), 4e-3);
+    let start_momentum = momentum(&system.bodies);
+
+    // About ten orbits of the outer planet
+    system.advance(400.0);
+
+    let drift = system.energy_drift().abs();
+    assert!(drift < 1e-5, "energy drift {drift:e}");
+    assert!((momentum(&system.bodies) - start_momentum).norm() < 1e-12);
+  }
+
+  #[test]
+  fn stepping_backwards_retraces_the_motion() {
+    let (mut system, period) = sun_and_planet(0.3, 1e-2);
+    let start: Vec<DVec3> = system.bodies.iter().map(|body| body.position).collect();
+
+    system.advance(period * 3.0);
+    system.advance(-period * 3.0);
+
+    for (body, start) in system.bodies.iter().zip(&start) {
+      assert!((body.position - start).norm() < 1e-9, "ended at {:?}, started at {:?}", body.position, start);
+    }
+  }
+}
@@ -1,4 +1,3 @@
-use tobj;
 use nalgebra_glm::{Vec2, Vec3};
 use crate::vertex::Vertex;
 
@@ -6,6 +5,82 @@ pub struct Obj {
     meshes: Vec<Mesh>,
 }
 
+pub struct IndexedMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl IndexedMesh {
+    /// A flat annulus in the XZ plane between `inner_radius` and `outer_radius`, split
+    /// into `segments` around. u runs from 0 at the inner edge to 1 at the outer edge and
+    /// v once around. Both faces are built, with +Y and -Y normals and counter-clockwise
+    /// winding each, so back-face culling keeps the one facing the camera.
+    pub fn ring(inner_radius: f32, outer_radius: f32, segments: usize) -> IndexedMesh {
+        let segments = segments.max(3);
+        let mut vertices = Vec::with_capacity(4 * (segments + 1));
+        let mut indices = Vec::with_capacity(12 * segments);
+        for up in [1.0, -1.0] {
+            let normal = Vec3::new(0.0, up, 0.0);
+            let base = vertices.len() as u32;
+            // The seam is duplicated so v can reach 1
+            for k in 0..=segments {
+                let v = k as f32 / segments as f32;
+                let (sin, cos) = (v * std::f32::consts::TAU).sin_cos();
+                for (u, radius) in [(0.0, inner_radius), (1.0, outer_radius)] {
+                    vertices.push(Vertex::new(Vec3::new(radius * cos, 0.0, radius * sin), normal, Vec2::new(u, v)));
+                }
+            }
+            for k in 0..segments as u32 {
+                let (inner, outer, next_inner, next_outer) = (base + 2 * k, base + 2 * k + 1, base + 2 * k + 2, base + 2 * k + 3);
+                let quad = [inner, next_inner, outer, next_inner, next_outer, outer];
+                if up > 0.0 {
+                    indices.extend(quad);
+                } else {
+                    indices.extend(quad.iter().rev());
+                }
+            }
+        }
+        IndexedMesh { vertices, indices }
+    }
+
+    /// Per-vertex tangents (direction of increasing u) from the texture coordinates,
+    /// orthogonalized against each vertex normal. Vertices whose triangles have no
+    /// usable UVs get an arbitrary tangent perpendicular to the normal.
+    pub fn tangents(&self) -> Vec<Vec3> {
+        let mut tangents = vec![Vec3::zeros(); self.vertices.len()];
+        for tri in self.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let (va, vb, vc) = (&self.vertices[a], &self.vertices[b], &self.vertices[c]);
+            let (e1, e2) = (vb.position - va.position, vc.position - va.position);
+            let (d1, d2) = (vb.tex_coords - va.tex_coords, vc.tex_coords - va.tex_coords);
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let tangent = (e1 * d2.y - e2 * d1.y) / det;
+            for i in [a, b, c] {
+                tangents[i] += tangent;
+            }
+        }
+
+        tangents
+            .iter()
+            .zip(&self.vertices)
+            .map(|(tangent, vertex)| {
+                let n = vertex.normal.normalize();
+                let t = tangent - n * n.dot(tangent);
+                if t.norm() > 1e-6 {
+                    t.normalize()
+                } else {
+                    // Any direction in the tangent plane
+                    let axis = if n.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+                    n.cross(&axis).normalize()
+                }
+            })
+            .collect()
+    }
+}
+
 struct Mesh {
     vertices: Vec<Vec3>,
     normals: Vec<Vec3>,
@@ -23,7 +98,7 @@ impl Obj {
 
         let meshes = models.into_iter().map(|model| {
             let mesh = model.mesh;
-            Mesh {
+            let mut mesh = Mesh {
                 vertices: mesh.positions.chunks(3)
                     .map(|v| Vec3::new(v[0], -v[1], -v[2]))
                     .collect(),
@@ -34,29 +109,106 @@ impl Obj {
                     .map(|t| Vec2::new(t[0], 1.0 - t[1]))
                     .collect(),
                 indices: mesh.indices,
-            }
+            };
+            mesh.normalize_winding();
+            mesh
         }).collect();
 
         Ok(Obj { meshes })
     }
 
-    pub fn get_vertex_array(&self) -> Vec<Vertex> {
+    /// Returns every mesh merged into one vertex buffer plus an index buffer, so
+    /// vertices shared between triangles are only stored (and transformed) once.
+    pub fn get_indexed_mesh(&self) -> IndexedMesh {
         let mut vertices = Vec::new();
+        let mut indices = Vec::new();
 
         for mesh in &self.meshes {
-            for &index in &mesh.indices {
-                let position = mesh.vertices[index as usize];
-                let normal = mesh.normals.get(index as usize)
+            let base = vertices.len() as u32;
+            for (i, &position) in mesh.vertices.iter().enumerate() {
+                let normal = mesh.normals.get(i)
                     .cloned()
                     .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
-                let tex_coords = mesh.texcoords.get(index as usize)
+                let tex_coords = mesh.texcoords.get(i)
                     .cloned()
                     .unwrap_or(Vec2::new(0.0, 0.0));
 
                 vertices.push(Vertex::new(position, normal, tex_coords));
             }
+            indices.extend(mesh.indices.iter().map(|&index| base + index));
         }
 
-        vertices
+        IndexedMesh { vertices, indices }
+    }
+}
+
+impl Mesh {
+    // Negating Y and Z on load is a 180 degree rotation about X, so it keeps the
+    // handedness of the file. Triangles are compared against their (equally flipped)
+    // normals and reordered when needed so every mesh ends up counter-clockwise,
+    // which is what back-face culling assumes.
+    fn normalize_winding(&mut self) {
+        if self.normals.len() != self.vertices.len() {
+            return;
+        }
+
+        let mut counter_clockwise = 0;
+        let mut clockwise = 0;
+        for tri in self.indices.chunks(3) {
+            if tri.len() < 3 {
+                continue;
+            }
+            let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+            let face_normal = (self.vertices[b] - self.vertices[a]).cross(&(self.vertices[c] - self.vertices[a]));
+            let vertex_normal = self.normals[a] + self.normals[b] + self.normals[c];
+            if face_normal.dot(&vertex_normal) >= 0.0 {
+                counter_clockwise += 1;
+            } else {
+                clockwise += 1;
+            }
+        }
+
+        if clockwise > counter_clockwise {
+            for tri in self.indices.chunks_mut(3) {
+                if tri.len() == 3 {
+                    tri.swap(1, 2);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_uvs_span_the_annulus() {
+        let ring = IndexedMesh::ring(1.0, 2.0, 16);
+        for vertex in &ring.vertices {
+            let radius = vertex.position.xz().norm();
+            let (u, v) = (vertex.tex_coords.x, vertex.tex_coords.y);
+            assert!(u == 0.0 || u == 1.0, "u = {u}");
+            assert!((radius - (1.0 + u)).abs() < 1e-5, "u = {u} at radius {radius}");
+            assert!((0.0..=1.0).contains(&v), "v = {v}");
+            assert_eq!(vertex.position.y, 0.0);
+        }
+        assert!(ring.vertices.iter().any(|vertex| vertex.tex_coords.y == 1.0));
+    }
+
+    #[test]
+    fn ring_faces_wind_counter_clockwise_around_their_normals() {
+        let ring = IndexedMesh::ring(1.0, 2.0, 16);
+        let (mut up, mut down) = (0, 0);
+        for tri in ring.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| &ring.vertices[tri[k] as usize]);
+            let face = (b.position - a.position).cross(&(c.position - a.position));
+            assert!(face.norm() > 0.0);
+            for vertex in [a, b, c] {
+                assert!(face.dot(&vertex.normal) > 0.0, "triangle {tri:?} winds against its normal");
+            }
+            if a.normal.y > 0.0 { up += 1 } else { down += 1 }
+        }
+        assert_eq!((up, down), (32, 32));
     }
 }
@@ -0,0 +1,118 @@
+use nalgebra_glm::Vec3;
+use nalgebra_glm as glm;
+use std::f64::consts::TAU;
+
+/// Gravitational constant in scene units: world units, simulation seconds and a sun of
+/// mass 1.
+pub const GRAVITY: f64 = 1.0;
+
+/// Kepler's third law: the period of an orbit of `semi_major_axis` when the two bodies
+/// add up to `total_mass`.
+pub fn kepler_period(semi_major_axis: f32, total_mass: f32) -> f32 {
+  (TAU * ((semi_major_axis as f64).powi(3) / (GRAVITY * total_mass as f64)).sqrt()) as f32
+}
+
+/// Keplerian orbital elements of a body around its parent. The reference plane is the
+/// world XZ plane; angles are in radians and the period in simulation seconds.
+#[derive(Clone, Copy, Debug)]
+pub struct Orbit {
+  pub semi_major_axis: f32,
+  pub eccentricity: f32,
+  // Tilt of the orbital plane around the line of nodes
+  pub inclination: f32,
+  // Where the orbit crosses the reference plane going "up", measured around Y
+  pub ascending_node: f32,
+  // Angle from the ascending node to the periapsis, within the orbital plane
+  pub argument_of_periapsis: f32,
+  pub period: f32,
+  // Mean anomaly at time 0, so bodies don't all start lined up
+  pub phase: f32,
+}
+
+impl Orbit {
+  pub fn new(semi_major_axis: f32, eccentricity: f32, inclination: f32, period: f32) -> Self {
+    Orbit {
+      semi_major_axis,
+      eccentricity: eccentricity.clamp(0.0, 0.99),
+      inclination,
+      ascending_node: 0.0,
+      argument_of_periapsis: 0.0,
+      period,
+      phase: 0.0,
+    }
+  }
+
+  /// Position relative to the parent at simulation `time`. Negative times work the
+  /// same way, so the clock can run backwards.
+  pub fn position(&self, time: f64) -> Vec3 {
+    self.state(time).0
+  }
+
+  /// Position and velocity relative to the parent at simulation `time`.
+  pub fn state(&self, time: f64) -> (Vec3, Vec3) {
+    let mean_anomaly = if self.period != 0.0 {
+      (self.phase as f64 + TAU * time / self.period as f64).rem_euclid(TAU) as f32
+    } else {
+      self.phase
+    };
+    let e = self.eccentricity;
+    let eccentric_anomaly = solve_kepler(mean_anomaly, e);
+
+    // In the orbital plane with the periapsis on +X and the focus (parent) at the origin
+    let a = self.semi_major_axis;
+    let (sin_e, cos_e) = eccentric_anomaly.sin_cos();
+    let minor = (1.0 - e * e).sqrt();
+    let position = Vec3::new(a * (cos_e - e), 0.0, a * minor * sin_e);
+    // dE/dt follows from differentiating Kepler's equation
+    let mean_motion = if self.period != 0.0 { std::f32::consts::TAU / self.period } else { 0.0 };
+    let eccentric_rate = mean_motion / (1.0 - e * cos_e);
+    let velocity = Vec3::new(-a * sin_e, 0.0, a * minor * cos_e) * eccentric_rate;
+
+    (self.orient(&position), self.orient(&velocity))
+  }
+
+  // From the orbital plane to the parent's space
+  fn orient(&self, v: &Vec3) -> Vec3 {
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let periapsis = glm::rotate_vec3(v, self.argument_of_periapsis, &up);
+    let inclined = glm::rotate_vec3(&periapsis, self.inclination, &Vec3::new(1.0, 0.0, 0.0));
+    glm::rotate_vec3(&inclined, self.ascending_node, &up)
+  }
+}
+
+/// Solves Kepler's equation M = E - e sin E for the eccentric anomaly E with Newton's
+/// method. Converges in a handful of steps for any eccentricity below 1.
+pub fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+  let (m, e) = (mean_anomaly as f64, eccentricity as f64);
+  // Starting from M stalls for very eccentric orbits; pi is safe there
+  let mut eccentric = if e < 0.8 { m } else { std::f64::consts::PI };
+  for _ in 0..16 {
+    let step = (eccentric - e * eccentric.sin() - m) / (1.0 - e * eccentric.cos());
+    eccentric -= step;
+    if step.abs() < 1e-9 {
+      break;
+    }
+  }
+  eccentric as f32
+}
+
+/// Rotation of a body around its own axis, which is tilted away from the orbit normal.
+#[derive(Clone, Copy, Debug)]
+pub struct Spin {
+  // Simulation seconds per turn
+  pub period: f32,
+  pub axial_tilt: f32,
+}
+
+impl Spin {
+  /// Euler angles for `create_model_matrix`: the Y spin is applied first, then the tilt
+  /// around Z, so the body turns around its tilted axis.
+  pub fn rotation(&self, time: f64) -> Vec3 {
+    let angle = if self.period != 0.0 {
+      (TAU * time / self.period as f64).rem_euclid(TAU) as f32
+    } else {
+      0.0
+    };
+    Vec3::new(0.0, angle, self.axial_tilt)
+  }
+}
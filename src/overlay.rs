@@ -0,0 +1,96 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use nalgebra_glm as glm;
+use crate::Uniforms;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::line::{line, LineStyle};
+use crate::obj::IndexedMesh;
+use crate::scene::Scene;
+use crate::shaders::clip_to_screen;
+use crate::vertex::Vertex;
+
+const TANGENT_COLOR: (u8, u8, u8) = (255, 200, 40);
+
+/// Draws vertex normals (and optionally tangents) as short line segments on top of the
+/// frame, depth-tested so hidden vertices stay hidden. Normals are colored by their
+/// world-space direction, xyz -> rgb like the normals debug view.
+pub struct NormalOverlay {
+  pub show_normals: bool,
+  pub show_tangents: bool,
+  // Segment length in world units
+  pub length: f32,
+  // Draw every `stride`-th vertex
+  pub stride: usize,
+  pub style: LineStyle,
+  // Per mesh of the scene, per vertex
+  tangents: Vec<Vec<Vec3>>,
+}
+
+impl NormalOverlay {
+  pub fn new(meshes: &[IndexedMesh]) -> Self {
+    NormalOverlay {
+      show_normals: false,
+      show_tangents: false,
+      length: 0.05,
+      stride: 1,
+      style: LineStyle::default(),
+      tangents: meshes.iter().map(IndexedMesh::tangents).collect(),
+    }
+  }
+
+  /// Draws the overlay for every mesh in `scene`; `frame` holds the camera matrices.
+  pub fn draw(&self, framebuffer: &mut Framebuffer, frame: &Uniforms, scene: &Scene) {
+    if !self.show_normals && !self.show_tangents {
+      return;
+    }
+    for item in scene.draw_list() {
+      let uniforms = frame.with_model(item.model_matrix);
+      self.draw_mesh(framebuffer, &uniforms, &scene.meshes[item.mesh], &self.tangents[item.mesh]);
+    }
+  }
+
+  fn draw_mesh(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, mesh: &IndexedMesh, tangents: &[Vec3]) {
+    let model = &uniforms.model_matrix;
+    let model_3x3 = glm::mat4_to_mat3(model);
+    let view_projection = uniforms.projection_matrix * uniforms.view_matrix;
+
+    for (i, vertex) in mesh.vertices.iter().enumerate().step_by(self.stride.max(1)) {
+      let world = model * Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+      let origin = Vec3::new(world.x, world.y, world.z);
+
+      if self.show_normals {
+        let normal = (uniforms.normal_matrix * vertex.normal).normalize();
+        let direction = normal * 0.5 + Vec3::new(0.5, 0.5, 0.5);
+        self.segment(framebuffer, uniforms, &view_projection, origin, normal, Color::from_vec3(direction));
+      }
+      if self.show_tangents {
+        // Tangents follow the surface, so they use the model matrix itself
+        let tangent = (model_3x3 * tangents[i]).normalize();
+        let (r, g, b) = TANGENT_COLOR;
+        self.segment(framebuffer, uniforms, &view_projection, origin, tangent, Color::new(r, g, b));
+      }
+    }
+  }
+
+  fn segment(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, view_projection: &Mat4, origin: Vec3, direction: Vec3, color: Color) {
+    let to_vertex = |point: Vec3| {
+      let clip_position = view_projection * Vec4::new(point.x, point.y, point.z, 1.0);
+      Vertex {
+        clip_position,
+        transformed_position: clip_to_screen(&clip_position, &uniforms.viewport_matrix),
+        color,
+        ..Vertex::default()
+      }
+    };
+    let start = to_vertex(origin);
+    let end = to_vertex(origin + direction * self.length);
+    // Both ends must be in front of the camera; line() takes care of the screen edges
+    if start.clip_position.w <= 0.0 || end.clip_position.w <= 0.0 {
+      return;
+    }
+
+    for fragment in line(&start, &end, &self.style, framebuffer.width, framebuffer.height) {
+      framebuffer.draw_fragment(&fragment);
+    }
+  }
+}
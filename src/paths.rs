@@ -0,0 +1,160 @@
+use nalgebra_glm::{Vec3, DVec3, Vec4, Mat4};
+use nalgebra_glm as glm;
+use crate::Uniforms;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::line::{line, LineStyle};
+use crate::clipping::clip_line;
+use crate::nbody::NBody;
+use crate::scene::{Scene, BodyInfo};
+use crate::shaders::clip_to_screen;
+use crate::vertex::Vertex;
+
+// Segments per orbit ellipse or predicted trajectory
+const PATH_SEGMENTS: usize = 128;
+
+// One per body, in the order of Scene::bodies
+const PATH_COLORS: [(u8, u8, u8); 6] = [
+  (255, 200, 80),
+  (120, 200, 255),
+  (255, 150, 110),
+  (160, 255, 160),
+  (220, 160, 255),
+  (255, 255, 255),
+];
+
+/// Orbit ellipses of the scripted orbits, or predicted trajectories in N-body mode,
+/// drawn as depth-tested polylines that start at the body and fade out along the path.
+pub struct PathOverlay {
+  // Per body, in the order of Scene::bodies
+  pub visible: Vec<bool>,
+  // Body that `toggle` affects; None toggles all of them
+  pub selected: Option<usize>,
+  // How far ahead trajectories are predicted, in simulation seconds
+  pub prediction: f64,
+  pub style: LineStyle,
+  // Last prediction and what it was made from: simulation step count, duration
+  predicted: Option<(i64, f64, Vec<Vec<DVec3>>)>,
+}
+
+impl PathOverlay {
+  pub fn new(body_count: usize) -> Self {
+    PathOverlay {
+      visible: vec![false; body_count],
+      selected: None,
+      prediction: 40.0,
+      style: LineStyle { antialiased: true, ..LineStyle::default() },
+      predicted: None,
+    }
+  }
+
+  /// Cycles the selection: all bodies, then each body in turn.
+  pub fn select_next(&mut self) {
+    self.selected = match self.selected {
+      None if !self.visible.is_empty() => Some(0),
+      Some(i) if i + 1 < self.visible.len() => Some(i + 1),
+      _ => None,
+    };
+  }
+
+  pub fn toggle(&mut self) {
+    match self.selected {
+      Some(i) => self.visible[i] = !self.visible[i],
+      None => {
+        let show = !self.visible.iter().any(|&visible| visible);
+        self.visible.iter_mut().for_each(|visible| *visible = show);
+      }
+    }
+  }
+
+  /// Draws the visible paths. Without a `simulation` each body's orbit is traced back
+  /// one period from `time`; with one, its trajectory is predicted in the direction
+  /// time is running (`direction` < 0 in reverse). Moons are drawn relative to their
+  /// planet so they trace loops around it instead of around the sun. The prediction is
+  /// only redone once the simulation has stepped.
+  pub fn draw(&mut self, framebuffer: &mut Framebuffer, frame: &Uniforms, scene: &Scene, time: f64, simulation: Option<&NBody>, direction: f64) {
+    let Some(simulation) = simulation else {
+      self.predicted = None;
+      return self.draw_paths(framebuffer, frame, scene, time, None);
+    };
+    let duration = self.prediction * direction.signum();
+    let stale = !matches!(&self.predicted, Some((steps, cached, _)) if *steps == simulation.steps() && *cached == duration);
+    if stale && self.visible.iter().any(|&visible| visible) {
+      self.predicted = Some((simulation.steps(), duration, simulation.predict(duration, PATH_SEGMENTS)));
+    }
+    let predicted = self.predicted.as_ref().map(|(_, _, paths)| paths.as_slice());
+    self.draw_paths(framebuffer, frame, scene, time, predicted);
+  }
+
+  fn draw_paths(&self, framebuffer: &mut Framebuffer, frame: &Uniforms, scene: &Scene, time: f64, predicted: Option<&[Vec<DVec3>]>) {
+    if !self.visible.iter().any(|&visible| visible) {
+      return;
+    }
+    let bodies = scene.body_info();
+
+    // Paths are in root space, so they follow the view transform like the bodies do
+    let uniforms = frame.with_model(scene.root.local_matrix());
+    let transform = uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix;
+
+    for (i, body) in bodies.iter().enumerate() {
+      if !self.visible.get(i).copied().unwrap_or(false) {
+        continue;
+      }
+      let points = match predicted {
+        Some(paths) => trajectory(paths, &bodies, i),
+        None => orbit_path(body, &bodies, time),
+      };
+      let (r, g, b) = PATH_COLORS[i % PATH_COLORS.len()];
+      self.polyline(framebuffer, &uniforms, &transform, &points, Color::new(r, g, b));
+    }
+  }
+
+  // Each segment takes the fade at its midpoint, from 1 at the body to 0 at the far
+  // end, and scales the coverage alpha line() gives its fragments
+  fn polyline(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, transform: &Mat4, points: &[Vec3], color: Color) {
+    let to_vertex = |point: &Vec3| {
+      let clip_position = transform * Vec4::new(point.x, point.y, point.z, 1.0);
+      Vertex {
+        clip_position,
+        transformed_position: clip_to_screen(&clip_position, &uniforms.viewport_matrix),
+        color,
+        ..Vertex::default()
+      }
+    };
+    let segments = points.len().saturating_sub(1);
+
+    for (i, pair) in points.windows(2).enumerate() {
+      let Some([start, end]) = clip_line(&to_vertex(&pair[0]), &to_vertex(&pair[1]), &uniforms.viewport_matrix) else {
+        continue;
+      };
+      let fade = 1.0 - (i as f32 + 0.5) / segments as f32;
+      for mut fragment in line(&start, &end, &self.style, framebuffer.width, framebuffer.height) {
+        fragment.alpha *= fade;
+        framebuffer.draw_fragment(&fragment);
+      }
+    }
+  }
+}
+
+// The last period of the orbit, starting at the body, around where its parent is now
+fn orbit_path(body: &BodyInfo, bodies: &[BodyInfo], time: f64) -> Vec<Vec3> {
+  let Some(orbit) = body.orbit else {
+    return Vec::new();
+  };
+  let center = body.parent.map_or(Vec3::zeros(), |parent| bodies[parent].position);
+  (0..=PATH_SEGMENTS)
+    .map(|k| center + orbit.position(time - orbit.period as f64 * k as f64 / PATH_SEGMENTS as f64))
+    .collect()
+}
+
+// Predicted positions of body `i`, relative to its parent's predicted positions
+fn trajectory(paths: &[Vec<DVec3>], bodies: &[BodyInfo], i: usize) -> Vec<Vec3> {
+  let path = &paths[i];
+  match bodies[i].parent {
+    Some(parent) => {
+      let now = paths[parent][0];
+      path.iter().zip(&paths[parent]).map(|(point, center)| glm::convert(point - center + now)).collect()
+    }
+    None => path.iter().map(|point| glm::convert(*point)).collect(),
+  }
+}
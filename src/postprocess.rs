@@ -0,0 +1,331 @@
+use nalgebra_glm::Vec3;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+/// A full-screen effect applied after the frame has been rendered and resolved.
+/// Effects get the whole framebuffer, so they can read depth as well as color.
+pub trait PostEffect {
+  fn name(&self) -> &'static str;
+  fn apply(&mut self, framebuffer: &mut Framebuffer);
+}
+
+struct ChainEntry {
+  effect: Box<dyn PostEffect>,
+  enabled: bool,
+}
+
+/// Ordered list of post effects; each one can be switched on and off at runtime.
+#[derive(Default)]
+pub struct PostChain {
+  entries: Vec<ChainEntry>,
+}
+
+impl PostChain {
+  pub fn new() -> Self {
+    PostChain { entries: Vec::new() }
+  }
+
+  pub fn with(mut self, effect: impl PostEffect + 'static, enabled: bool) -> Self {
+    self.entries.push(ChainEntry { effect: Box::new(effect), enabled });
+    self
+  }
+
+  pub fn toggle(&mut self, index: usize) {
+    if let Some(entry) = self.entries.get_mut(index) {
+      entry.enabled = !entry.enabled;
+    }
+  }
+
+  pub fn enabled_names(&self) -> Vec<&'static str> {
+    self.entries.iter().filter(|e| e.enabled).map(|e| e.effect.name()).collect()
+  }
+
+  pub fn apply(&mut self, framebuffer: &mut Framebuffer) {
+    for entry in self.entries.iter_mut().filter(|e| e.enabled) {
+      entry.effect.apply(framebuffer);
+    }
+  }
+}
+
+fn pack(color: Vec3) -> u32 {
+  let r = (color.x * 255.0).round().clamp(0.0, 255.0) as u32;
+  let g = (color.y * 255.0).round().clamp(0.0, 255.0) as u32;
+  let b = (color.z * 255.0).round().clamp(0.0, 255.0) as u32;
+  (r << 16) | (g << 8) | b
+}
+
+fn luma(color: &Vec3) -> f32 {
+  0.299 * color.x + 0.587 * color.y + 0.114 * color.z
+}
+
+// Bilinear lookup with clamp-to-edge; (x, y) in pixel units where pixel centers sit at +0.5
+fn sample_bilinear<T>(data: &[T], width: usize, height: usize, x: f32, y: f32) -> T
+where
+  T: Copy + std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+  let fx = (x - 0.5).clamp(0.0, (width - 1) as f32);
+  let fy = (y - 0.5).clamp(0.0, (height - 1) as f32);
+  let (x0, y0) = (fx.floor() as usize, fy.floor() as usize);
+  let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+  let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+  let top = data[y0 * width + x0] * (1.0 - tx) + data[y0 * width + x1] * tx;
+  let bottom = data[y1 * width + x0] * (1.0 - tx) + data[y1 * width + x1] * tx;
+  top * (1.0 - ty) + bottom * ty
+}
+
+/// Fast approximate anti-aliasing: finds high-contrast luma edges, walks along them
+/// to estimate where they end and blends across the edge by the resulting coverage.
+pub struct Fxaa {
+  // Minimum local contrast, relative to the brightest neighbour, to treat as an edge
+  pub edge_threshold: f32,
+  // Absolute contrast floor so dark noise is left alone
+  pub edge_threshold_min: f32,
+  // How much sub-pixel aliasing (single pixel features) gets smoothed
+  pub subpixel_quality: f32,
+}
+
+impl Default for Fxaa {
+  fn default() -> Self {
+    Fxaa {
+      edge_threshold: 0.125,
+      edge_threshold_min: 0.0312,
+      subpixel_quality: 0.75,
+    }
+  }
+}
+
+const FXAA_SEARCH_STEPS: [f32; 12] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0];
+
+impl PostEffect for Fxaa {
+  fn name(&self) -> &'static str {
+    "FXAA"
+  }
+
+  fn apply(&mut self, framebuffer: &mut Framebuffer) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let colors: Vec<Vec3> = framebuffer.buffer.iter().map(|&c| Color::hex_to_vec3(c)).collect();
+    let lumas: Vec<f32> = colors.iter().map(luma).collect();
+    let at = |x: i32, y: i32| {
+      let x = x.clamp(0, width as i32 - 1) as usize;
+      let y = y.clamp(0, height as i32 - 1) as usize;
+      lumas[y * width + x]
+    };
+
+    for y in 0..height as i32 {
+      for x in 0..width as i32 {
+        let center = at(x, y);
+        let (north, south, west, east) = (at(x, y - 1), at(x, y + 1), at(x - 1, y), at(x + 1, y));
+        let luma_min = center.min(north).min(south).min(west).min(east);
+        let luma_max = center.max(north).max(south).max(west).max(east);
+        let range = luma_max - luma_min;
+        if range < self.edge_threshold_min.max(luma_max * self.edge_threshold) {
+          continue;
+        }
+
+        let (north_west, north_east) = (at(x - 1, y - 1), at(x + 1, y - 1));
+        let (south_west, south_east) = (at(x - 1, y + 1), at(x + 1, y + 1));
+
+        // Is the edge running horizontally or vertically?
+        let edge_horizontal = (-2.0 * west + north_west + south_west).abs()
+          + (-2.0 * center + north + south).abs() * 2.0
+          + (-2.0 * east + north_east + south_east).abs();
+        let edge_vertical = (-2.0 * north + north_west + north_east).abs()
+          + (-2.0 * center + west + east).abs() * 2.0
+          + (-2.0 * south + south_west + south_east).abs();
+        let horizontal = edge_horizontal >= edge_vertical;
+
+        // Pick the side of the pixel the edge lies on
+        let (luma1, luma2) = if horizontal { (north, south) } else { (west, east) };
+        let (gradient1, gradient2) = (luma1 - center, luma2 - center);
+        let steepest_is_1 = gradient1.abs() >= gradient2.abs();
+        let gradient_scaled = 0.25 * gradient1.abs().max(gradient2.abs());
+        let (step, local_average) = if steepest_is_1 {
+          (-1.0, 0.5 * (luma1 + center))
+        } else {
+          (1.0, 0.5 * (luma2 + center))
+        };
+
+        // Start half a pixel towards the edge, then walk both ways along it
+        let (mut ux, mut uy) = (x as f32 + 0.5, y as f32 + 0.5);
+        if horizontal {
+          uy += step * 0.5;
+        } else {
+          ux += step * 0.5;
+        }
+        let (dx, dy) = if horizontal { (1.0, 0.0) } else { (0.0, 1.0) };
+
+        let (mut ax, mut ay, mut bx, mut by) = (ux - dx, uy - dy, ux + dx, uy + dy);
+        let mut end_a = sample_bilinear(&lumas, width, height, ax, ay) - local_average;
+        let mut end_b = sample_bilinear(&lumas, width, height, bx, by) - local_average;
+        let mut reached_a = end_a.abs() >= gradient_scaled;
+        let mut reached_b = end_b.abs() >= gradient_scaled;
+        for &search_step in FXAA_SEARCH_STEPS.iter() {
+          if reached_a && reached_b {
+            break;
+          }
+          if !reached_a {
+            ax -= dx * search_step;
+            ay -= dy * search_step;
+            end_a = sample_bilinear(&lumas, width, height, ax, ay) - local_average;
+            reached_a = end_a.abs() >= gradient_scaled;
+          }
+          if !reached_b {
+            bx += dx * search_step;
+            by += dy * search_step;
+            end_b = sample_bilinear(&lumas, width, height, bx, by) - local_average;
+            reached_b = end_b.abs() >= gradient_scaled;
+          }
+        }
+
+        // Distance to each end of the edge decides how far to blend
+        let px = x as f32 + 0.5;
+        let py = y as f32 + 0.5;
+        let distance_a = if horizontal { px - ax } else { py - ay };
+        let distance_b = if horizontal { bx - px } else { by - py };
+        let closest_is_a = distance_a < distance_b;
+        let closest_distance = distance_a.min(distance_b);
+        let edge_length = distance_a + distance_b;
+        let pixel_offset = -closest_distance / edge_length + 0.5;
+
+        // Only blend when the nearer end actually bends away from this pixel
+        let center_smaller = center < local_average;
+        let end_luma = if closest_is_a { end_a } else { end_b };
+        let correct_variation = (end_luma < 0.0) != center_smaller;
+        let edge_offset = if correct_variation { pixel_offset } else { 0.0 };
+
+        // Sub-pixel anti-aliasing from the 3x3 neighbourhood average
+        let average = (2.0 * (north + south + west + east) + north_west + north_east + south_west + south_east) / 12.0;
+        let subpixel1 = ((average - center).abs() / range).clamp(0.0, 1.0);
+        let subpixel2 = (-2.0 * subpixel1 + 3.0) * subpixel1 * subpixel1;
+        let subpixel_offset = subpixel2 * subpixel2 * self.subpixel_quality;
+
+        let offset = edge_offset.max(subpixel_offset) * step;
+        let (sx, sy) = if horizontal { (px, py + offset) } else { (px + offset, py) };
+        let color = sample_bilinear(&colors, width, height, sx, sy);
+        framebuffer.buffer[y as usize * width + x as usize] = pack(color);
+      }
+    }
+  }
+}
+
+/// Darkens the frame towards the corners.
+pub struct Vignette {
+  // Darkening at the very corners, 0..1
+  pub strength: f32,
+  // Normalized distance from the center where darkening starts
+  pub radius: f32,
+}
+
+impl Default for Vignette {
+  fn default() -> Self {
+    Vignette { strength: 0.45, radius: 0.55 }
+  }
+}
+
+impl PostEffect for Vignette {
+  fn name(&self) -> &'static str {
+    "vignette"
+  }
+
+  fn apply(&mut self, framebuffer: &mut Framebuffer) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let (cx, cy) = (width as f32 * 0.5, height as f32 * 0.5);
+    // Distance 1.0 is the corner
+    let max_distance = (cx * cx + cy * cy).sqrt();
+
+    for y in 0..height {
+      for x in 0..width {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let distance = (dx * dx + dy * dy).sqrt() / max_distance;
+        let t = ((distance - self.radius) / (1.0 - self.radius)).clamp(0.0, 1.0);
+        let falloff = t * t * (3.0 - 2.0 * t); // smoothstep
+        let index = y * width + x;
+        framebuffer.buffer[index] = pack(Color::hex_to_vec3(framebuffer.buffer[index]) * (1.0 - self.strength * falloff));
+      }
+    }
+  }
+}
+
+/// Animated luminance noise, changing every frame.
+pub struct FilmGrain {
+  pub intensity: f32,
+  frame: u32,
+}
+
+impl Default for FilmGrain {
+  fn default() -> Self {
+    FilmGrain { intensity: 0.06, frame: 0 }
+  }
+}
+
+// Integer hash (lowbias32) mapped to [0,1)
+fn hash_noise(x: u32, y: u32, frame: u32) -> f32 {
+  let mut h = x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ frame.wrapping_mul(0xcb1a_b31f);
+  h ^= h >> 16;
+  h = h.wrapping_mul(0x7feb_352d);
+  h ^= h >> 15;
+  h = h.wrapping_mul(0x846c_a68b);
+  h ^= h >> 16;
+  (h >> 8) as f32 / (1u32 << 24) as f32
+}
+
+impl PostEffect for FilmGrain {
+  fn name(&self) -> &'static str {
+    "film grain"
+  }
+
+  fn apply(&mut self, framebuffer: &mut Framebuffer) {
+    self.frame = self.frame.wrapping_add(1);
+    let width = framebuffer.width;
+
+    for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+      let (x, y) = ((index % width) as u32, (index / width) as u32);
+      let noise = (hash_noise(x, y, self.frame) - 0.5) * self.intensity;
+      let color = Color::hex_to_vec3(*pixel);
+      // Grain is most visible in the mid tones, like real film
+      let response = 1.0 - (luma(&color) * 2.0 - 1.0).abs() * 0.5;
+      *pixel = pack(color.add_scalar(noise * response));
+    }
+  }
+}
+
+/// Lens-style color fringing: red and blue are sampled slightly outwards / inwards
+/// from the center, growing towards the edges of the frame.
+pub struct ChromaticAberration {
+  // Channel offset in pixels at the corners
+  pub strength: f32,
+}
+
+impl Default for ChromaticAberration {
+  fn default() -> Self {
+    ChromaticAberration { strength: 3.0 }
+  }
+}
+
+impl PostEffect for ChromaticAberration {
+  fn name(&self) -> &'static str {
+    "chromatic aberration"
+  }
+
+  fn apply(&mut self, framebuffer: &mut Framebuffer) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let colors: Vec<Vec3> = framebuffer.buffer.iter().map(|&c| Color::hex_to_vec3(c)).collect();
+    let (cx, cy) = (width as f32 * 0.5, height as f32 * 0.5);
+    let max_distance = (cx * cx + cy * cy).sqrt();
+
+    for y in 0..height {
+      for x in 0..width {
+        let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+        // Offset grows with the distance from the center, pointing outwards
+        let (dx, dy) = ((px - cx) / max_distance, (py - cy) / max_distance);
+        let (ox, oy) = (dx * self.strength, dy * self.strength);
+
+        let red = sample_bilinear(&colors, width, height, px + ox, py + oy).x;
+        let green = colors[y * width + x].y;
+        let blue = sample_bilinear(&colors, width, height, px - ox, py - oy).z;
+        framebuffer.buffer[y * width + x] = pack(Vec3::new(red, green, blue));
+      }
+    }
+  }
+}
@@ -0,0 +1,324 @@
+use nalgebra_glm::{Vec3, DVec3, Mat4};
+use nalgebra_glm as glm;
+use crate::create_model_matrix;
+use crate::nbody::Body;
+use crate::obj::IndexedMesh;
+use crate::orbit::{Orbit, Spin, kepler_period};
+use crate::shaders::{Material, RingShadow};
+
+/// A node of the scene graph. Its transform is relative to its parent, so moving a
+/// planet carries its moons along with it.
+pub struct Node {
+  pub name: &'static str,
+  // Index into Scene::meshes; None for nodes that only group or offset children
+  pub mesh: Option<usize>,
+  pub material: Material,
+  pub translation: Vec3,
+  pub rotation: Vec3,
+  pub scale: f32,
+  // When set, `Scene::update` drives the translation / rotation from the clock
+  pub orbit: Option<Orbit>,
+  pub spin: Option<Spin>,
+  // Nodes with mass take part in the N-body simulation
+  pub mass: f32,
+  // Drawn around the mesh with the same transform
+  pub rings: Option<Rings>,
+  pub children: Vec<Node>,
+}
+
+/// A ring system around a body's mesh. Radii are in the mesh's model units.
+#[derive(Clone, Copy, Debug)]
+pub struct Rings {
+  // Index into Scene::meshes, an annulus in the model XZ plane
+  pub mesh: usize,
+  pub planet_radius: f32,
+  pub inner_radius: f32,
+  pub outer_radius: f32,
+}
+
+impl Node {
+  /// A node without a mesh, placed at `translation` in its parent's space.
+  pub fn pivot(translation: Vec3) -> Self {
+    Node {
+      name: "",
+      mesh: None,
+      material: Material::default(),
+      translation,
+      rotation: Vec3::zeros(),
+      scale: 1.0,
+      orbit: None,
+      spin: None,
+      mass: 0.0,
+      rings: None,
+      children: Vec::new(),
+    }
+  }
+
+  /// A visible node drawing `mesh` with `material`, centered on its parent.
+  pub fn body(mesh: usize, material: Material, scale: f32) -> Self {
+    Node { mesh: Some(mesh), material, scale, ..Node::pivot(Vec3::zeros()) }
+  }
+
+  /// A node without a mesh that follows `orbit` around its parent.
+  pub fn orbiting(orbit: Orbit) -> Self {
+    Node { orbit: Some(orbit), ..Node::pivot(orbit.position(0.0)) }
+  }
+
+  pub fn with_spin(mut self, spin: Spin) -> Self {
+    self.rotation = spin.rotation(0.0);
+    self.spin = Some(spin);
+    self
+  }
+
+  pub fn with_name(mut self, name: &'static str) -> Self {
+    self.name = name;
+    self
+  }
+
+  pub fn with_mass(mut self, mass: f32) -> Self {
+    self.mass = mass;
+    self
+  }
+
+  pub fn with_rings(mut self, rings: Rings) -> Self {
+    self.rings = Some(rings);
+    self
+  }
+
+  pub fn with_child(mut self, child: Node) -> Self {
+    self.children.push(child);
+    self
+  }
+
+  pub fn local_matrix(&self) -> Mat4 {
+    create_model_matrix(self.translation, self.scale, self.rotation)
+  }
+}
+
+/// A mesh to draw and where, as found by `Scene::draw_list`.
+pub struct DrawItem {
+  pub mesh: usize,
+  pub material: Material,
+  pub model_matrix: Mat4,
+  // World position of the light, the center of the first sun in the scene
+  pub sun: Vec3,
+  // The node's ring system in world space, on the body and on the rings alike
+  pub rings: Option<RingShadow>,
+}
+
+/// A node with mass as seen by the path overlay, in the order of `Scene::bodies`.
+pub struct BodyInfo {
+  pub name: &'static str,
+  pub orbit: Option<Orbit>,
+  // Index of the body it orbits, if that one has mass too
+  pub parent: Option<usize>,
+  // Current position relative to the root
+  pub position: Vec3,
+}
+
+/// Meshes shared by index plus the node hierarchy that places them.
+pub struct Scene {
+  pub meshes: Vec<IndexedMesh>,
+  pub root: Node,
+}
+
+impl Scene {
+  /// Places every orbiting or spinning node where it is at simulation `time`.
+  pub fn update(&mut self, time: f64) {
+    animate(&mut self.root, time);
+  }
+
+  /// State vectors of every node with mass at `time`, following the scripted orbits.
+  /// Positions are relative to the root, so moving the view doesn't move the bodies.
+  pub fn bodies(&self, time: f64) -> Vec<Body> {
+    let mut bodies = Vec::new();
+    for child in &self.root.children {
+      collect_bodies(child, DVec3::zeros(), DVec3::zeros(), time, &mut bodies);
+    }
+    bodies
+  }
+
+  /// Name, orbit, parent and current position of every node with mass.
+  pub fn body_info(&self) -> Vec<BodyInfo> {
+    let mut info = Vec::new();
+    for child in &self.root.children {
+      collect_info(child, Vec3::zeros(), None, &mut info);
+    }
+    info
+  }
+
+  /// Moves the nodes with mass to simulated positions, given in the order of `bodies`.
+  pub fn set_body_positions(&mut self, bodies: &[Body]) {
+    let mut bodies = bodies.iter();
+    for child in &mut self.root.children {
+      place_bodies(child, DVec3::zeros(), &mut bodies);
+    }
+  }
+
+  /// Walks the hierarchy depth-first, composing each node's transform with its
+  /// parent's, and returns every node that has a mesh, each lit by the sun.
+  pub fn draw_list(&self) -> Vec<DrawItem> {
+    let mut items = Vec::new();
+    collect(&self.root, &Mat4::identity(), &mut items);
+    let sun = items
+      .iter()
+      .find(|item| item.material == Material::Sun)
+      .map_or(Vec3::zeros(), |item| item.model_matrix.column(3).xyz());
+    for item in &mut items {
+      item.sun = sun;
+    }
+    items
+  }
+}
+
+fn animate(node: &mut Node, time: f64) {
+  if let Some(orbit) = &node.orbit {
+    node.translation = orbit.position(time);
+  }
+  if let Some(spin) = &node.spin {
+    node.rotation = spin.rotation(time);
+  }
+  for child in &mut node.children {
+    animate(child, time);
+  }
+}
+
+// Nodes with mass are pivots that only translate, so a child's scene position is its
+// parent's plus its own translation
+fn collect_bodies(node: &Node, origin: DVec3, origin_velocity: DVec3, time: f64, bodies: &mut Vec<Body>) {
+  let (offset, velocity) = match &node.orbit {
+    Some(orbit) => orbit.state(time),
+    None => (node.translation, Vec3::zeros()),
+  };
+  let position = origin + glm::convert::<Vec3, DVec3>(offset);
+  let velocity = origin_velocity + glm::convert::<Vec3, DVec3>(velocity);
+  if node.mass > 0.0 {
+    bodies.push(Body { mass: node.mass as f64, position, velocity });
+  }
+  for child in &node.children {
+    collect_bodies(child, position, velocity, time, bodies);
+  }
+}
+
+fn collect_info(node: &Node, origin: Vec3, parent: Option<usize>, info: &mut Vec<BodyInfo>) {
+  let position = origin + node.translation;
+  let mut parent = parent;
+  if node.mass > 0.0 {
+    info.push(BodyInfo { name: node.name, orbit: node.orbit, parent, position });
+    parent = Some(info.len() - 1);
+  }
+  for child in &node.children {
+    collect_info(child, position, parent, info);
+  }
+}
+
+fn place_bodies<'a>(node: &mut Node, origin: DVec3, bodies: &mut impl Iterator<Item = &'a Body>) {
+  if node.mass > 0.0 && let Some(body) = bodies.next() {
+    node.translation = glm::convert(body.position - origin);
+  }
+  let position = origin + glm::convert::<Vec3, DVec3>(node.translation);
+  for child in &mut node.children {
+    place_bodies(child, position, bodies);
+  }
+}
+
+fn collect(node: &Node, parent: &Mat4, items: &mut Vec<DrawItem>) {
+  let world = parent * node.local_matrix();
+  let rings = node.rings.map(|rings| ring_shadow(&rings, &world));
+  if let Some(mesh) = node.mesh {
+    items.push(DrawItem { mesh, material: node.material, model_matrix: world, sun: Vec3::zeros(), rings });
+  }
+  if let Some(ring) = node.rings {
+    items.push(DrawItem { mesh: ring.mesh, material: Material::Ring, model_matrix: world, sun: Vec3::zeros(), rings });
+  }
+  for child in &node.children {
+    collect(child, &world, items);
+  }
+}
+
+// Node transforms are uniformly scaled rotations plus a translation, so the rings keep
+// their shape in world space
+fn ring_shadow(rings: &Rings, world: &Mat4) -> RingShadow {
+  let scale = world.column(0).xyz().magnitude();
+  RingShadow {
+    center: world.column(3).xyz(),
+    normal: world.column(1).xyz().normalize(),
+    planet_radius: rings.planet_radius * scale,
+    inner_radius: rings.inner_radius * scale,
+    outer_radius: rings.outer_radius * scale,
+  }
+}
+
+// The sphere model has a radius of ~50 units; these scales give radii in world units
+const SPHERE_RADIUS: f32 = 50.0;
+
+const SUN_MASS: f32 = 1.0;
+const ROCKY_MASS: f32 = 2e-3;
+const GAS_GIANT_MASS: f32 = 0.1;
+const MOON_MASS: f32 = 1e-4;
+
+// Ring radii of the gas giant, in planet radii
+const RING_INNER: f32 = 1.15;
+const RING_OUTER: f32 = 1.6;
+
+/// A sun with two planets and the gas giant's moons, all drawn with `sphere`, plus the
+/// gas giant's rings. Each body is a pivot with mass holding the visible mesh, so the
+/// mesh's scale and spin don't affect its moons. Periods follow Kepler's third law for
+/// these masses, so switching to the N-body simulation starts on the same orbits. The
+/// moons sit well inside the gas giant's Hill sphere (the outer one is retrograde) so
+/// they stay bound there too, and outside the rings. The rocky planet has no moon: at
+/// its mass the Hill sphere (~0.13) is smaller than the planet itself, so the N-body
+/// simulation would pull any moon away from it.
+pub fn solar_system(sphere: IndexedMesh) -> Scene {
+  let radius = |r: f32| r / SPHERE_RADIUS;
+  let orbit = |semi_major_axis: f32, eccentricity: f32, inclination: f32, masses: f32| {
+    Orbit::new(semi_major_axis, eccentricity, inclination, kepler_period(semi_major_axis, masses))
+  };
+
+  // Tidally locked: one turn per orbit in the orbit's direction. A positive spin turns
+  // +X towards -Z, the opposite way to a prograde orbit.
+  let locked = |orbit: &Orbit| Spin { period: -orbit.period * orbit.inclination.cos().signum(), axial_tilt: 0.0 };
+
+  let sun = Node::pivot(Vec3::zeros())
+    .with_name("sun")
+    .with_mass(SUN_MASS)
+    .with_child(Node::body(0, Material::Sun, radius(0.7)).with_spin(Spin { period: 30.0, axial_tilt: 0.12 }));
+
+  let rocky = Node::orbiting(Orbit { phase: 2.5, argument_of_periapsis: 0.8, ..orbit(1.5, 0.1, 0.06, SUN_MASS + ROCKY_MASS) })
+    .with_name("rocky planet")
+    .with_mass(ROCKY_MASS)
+    .with_child(Node::body(0, Material::Rock, radius(0.18)).with_spin(Spin { period: 6.0, axial_tilt: 0.4 }));
+
+  let inner_moon = orbit(0.55, 0.02, 0.1, GAS_GIANT_MASS + MOON_MASS);
+  let outer_moon = Orbit { phase: 3.0, ..orbit(0.76, 0.05, std::f32::consts::PI - 0.25, GAS_GIANT_MASS + MOON_MASS) };
+  let gas_giant = Node::orbiting(Orbit { ascending_node: 1.0, ..orbit(4.5, 0.06, 0.04, SUN_MASS + GAS_GIANT_MASS) })
+    .with_name("gas giant")
+    .with_mass(GAS_GIANT_MASS)
+    .with_child(
+      Node::body(0, Material::Gas, radius(0.3))
+        .with_spin(Spin { period: 4.0, axial_tilt: 0.45 })
+        .with_rings(Rings {
+          mesh: 1,
+          planet_radius: SPHERE_RADIUS,
+          inner_radius: SPHERE_RADIUS * RING_INNER,
+          outer_radius: SPHERE_RADIUS * RING_OUTER,
+        }),
+    )
+    .with_child(
+      Node::orbiting(inner_moon)
+        .with_name("inner moon")
+        .with_mass(MOON_MASS)
+        .with_child(Node::body(0, Material::Rock, radius(0.07)).with_spin(locked(&inner_moon))),
+    )
+    .with_child(
+      Node::orbiting(outer_moon)
+        .with_name("outer moon")
+        .with_mass(MOON_MASS)
+        .with_child(Node::body(0, Material::Rock, radius(0.05)).with_spin(locked(&outer_moon))),
+    );
+
+  Scene {
+    meshes: vec![sphere, IndexedMesh::ring(SPHERE_RADIUS * RING_INNER, SPHERE_RADIUS * RING_OUTER, 128)],
+    root: Node::pivot(Vec3::zeros()).with_child(sun).with_child(rocky).with_child(gas_giant),
+  }
+}
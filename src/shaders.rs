@@ -1,18 +1,120 @@
-use nalgebra_glm::{Vec3, Vec4, Mat3};
+use nalgebra_glm::{Vec3, Vec4, Mat4};
 use crate::vertex::Vertex;
 use crate::Uniforms;
+use crate::color::{srgb, srgb_to_linear, get_gamma_correct};
 use nalgebra_glm as glm;
-use std::sync::atomic::{AtomicUsize, AtomicU32, Ordering};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
 
-static CURRENT_SHADER: AtomicUsize = AtomicUsize::new(0);
+// Anything outside Material::ALL means "no override"
+static MATERIAL_OVERRIDE: AtomicU8 = AtomicU8::new(u8::MAX);
 static NOISE_SEED: AtomicU32 = AtomicU32::new(0);
+static DEBUG_VIEW: AtomicU8 = AtomicU8::new(DebugView::Off as u8);
+// Nearest and farthest view depth drawn this frame, as f32 bits
+static DEPTH_RANGE: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Replaces the procedural shaders with a view of the interpolated pipeline data,
+/// to tell apart a broken shader from broken normals, positions or depth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugView {
+  Off,
+  // World-space normal, xyz -> rgb
+  Normals,
+  // Model-space position, repeating every POSITION_PERIOD units
+  Position,
+  // View-space depth across the frame's depth range, white nearest and dim farthest
+  LinearDepth,
+  // Perspective-correct barycentric coordinates
+  Barycentrics,
+  TexCoords,
+  // A distinct color per mesh triangle
+  TriangleId,
+}
+
+impl DebugView {
+  const ALL: [DebugView; 7] = [
+    DebugView::Off,
+    DebugView::Normals,
+    DebugView::Position,
+    DebugView::LinearDepth,
+    DebugView::Barycentrics,
+    DebugView::TexCoords,
+    DebugView::TriangleId,
+  ];
+
+  pub fn next(self) -> Self {
+    DebugView::ALL[(self as usize + 1) % DebugView::ALL.len()]
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      DebugView::Off => "off",
+      DebugView::Normals => "normals",
+      DebugView::Position => "position",
+      DebugView::LinearDepth => "linear depth",
+      DebugView::Barycentrics => "barycentrics",
+      DebugView::TexCoords => "tex coords",
+      DebugView::TriangleId => "triangle id",
+    }
+  }
+}
+
+pub fn set_debug_view(view: DebugView) {
+  DEBUG_VIEW.store(view as u8, Ordering::Relaxed);
+}
+
+pub fn get_debug_view() -> DebugView {
+  DebugView::ALL[(DEBUG_VIEW.load(Ordering::Relaxed) as usize).min(DebugView::ALL.len() - 1)]
+}
 
-pub fn set_shader_index(idx: usize) {
-  CURRENT_SHADER.store(idx, Ordering::Relaxed);
+/// View-space depth range the linear depth view spreads over, normally the nearest
+/// and farthest vertex in the frame, so it stays readable wherever the camera is.
+pub fn set_depth_range(near: f32, far: f32) {
+  DEPTH_RANGE[0].store(near.to_bits(), Ordering::Relaxed);
+  DEPTH_RANGE[1].store(far.to_bits(), Ordering::Relaxed);
+}
+
+fn get_depth_range() -> (f32, f32) {
+  (f32::from_bits(DEPTH_RANGE[0].load(Ordering::Relaxed)), f32::from_bits(DEPTH_RANGE[1].load(Ordering::Relaxed)))
+}
+
+/// Surface shader of a scene node.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Material {
+  #[default]
+  Gas,
+  Rock,
+  Sun,
+  // Alpha-blended; never replaced by the override
+  Ring,
+}
+
+impl Material {
+  const ALL: [Material; 3] = [Material::Gas, Material::Rock, Material::Sun];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Material::Gas => "gas",
+      Material::Rock => "rock",
+      Material::Sun => "sun",
+      Material::Ring => "ring",
+    }
+  }
+
+  /// Transparent materials are drawn after the opaque ones, back to front, and don't
+  /// write depth.
+  pub fn is_transparent(self) -> bool {
+    self == Material::Ring
+  }
 }
 
-pub fn get_shader_index() -> usize {
-  CURRENT_SHADER.load(Ordering::Relaxed)
+// Some(material) shades every node with it, None goes back to each node's own material
+pub fn set_material_override(material: Option<Material>) {
+  MATERIAL_OVERRIDE.store(material.map_or(u8::MAX, |material| material as u8), Ordering::Relaxed);
+}
+
+pub fn get_material_override() -> Option<Material> {
+  Material::ALL.get(MATERIAL_OVERRIDE.load(Ordering::Relaxed) as usize).copied()
 }
 
 pub fn set_noise_seed(seed: u32) {
@@ -26,13 +128,108 @@ fn get_noise_seed() -> u32 {
 fn noise_seed_vec3() -> Vec3 {
   let s = get_noise_seed() as f32;
   // Pseudo-random generation via sin/fract trick
-  let r1 = ((s * 0.12345).sin() * 43758.5453).fract();
-  let r2 = ((s * 0.34567).sin() * 28123.1234).fract();
-  let r3 = ((s * 0.78901).sin() * 15937.9876).fract();
+  let r1 = ((s * 0.12345).sin() * 43758.547).fract();
+  let r2 = ((s * 0.34567).sin() * 28123.123).fract();
+  let r3 = ((s * 0.78901).sin() * 15937.987).fract();
   // Map [0,1) -> [-1,1]
   Vec3::new(r1 * 2.0 - 1.0, r2 * 2.0 - 1.0, r3 * 2.0 - 1.0)
 }
 
+/// Every constant color the shaders use, one field per color.
+struct Palette {
+  // planet_shader
+  ocean: Vec3,
+  plates: Vec3,
+  neon_cyan: Vec3,
+  neon_magenta: Vec3,
+
+  // planet_shader_gas
+  cream: Vec3,
+  tan: Vec3,
+  ochre: Vec3,
+  blue_gray: Vec3,
+  haze: Vec3,
+
+  // planet_shader_rock
+  basalt: Vec3,
+  regolith: Vec3,
+  iron_oxide: Vec3,
+  crater_rim: Vec3,
+
+  // planet_shader_sun
+  photosphere: Vec3,
+  turbulence: Vec3,
+  corona: Vec3,
+
+  // ring_shader
+  ice: Vec3,
+  dust: Vec3,
+}
+
+impl Palette {
+  fn map(&self, f: impl Fn(&Vec3) -> Vec3) -> Palette {
+    Palette {
+      ocean: f(&self.ocean),
+      plates: f(&self.plates),
+      neon_cyan: f(&self.neon_cyan),
+      neon_magenta: f(&self.neon_magenta),
+      cream: f(&self.cream),
+      tan: f(&self.tan),
+      ochre: f(&self.ochre),
+      blue_gray: f(&self.blue_gray),
+      haze: f(&self.haze),
+      basalt: f(&self.basalt),
+      regolith: f(&self.regolith),
+      iron_oxide: f(&self.iron_oxide),
+      crater_rim: f(&self.crater_rim),
+      photosphere: f(&self.photosphere),
+      turbulence: f(&self.turbulence),
+      corona: f(&self.corona),
+      ice: f(&self.ice),
+      dust: f(&self.dust),
+    }
+  }
+}
+
+// As authored, in sRGB
+static SRGB_PALETTE: Palette = Palette {
+  // planet_shader
+  ocean: Vec3::new(0.02, 0.05, 0.08),
+  plates: Vec3::new(0.10, 0.06, 0.16),
+  neon_cyan: Vec3::new(0.0, 0.95, 0.85),
+  neon_magenta: Vec3::new(0.95, 0.2, 0.85),
+
+  // planet_shader_gas
+  cream: Vec3::new(0.92, 0.88, 0.80),
+  tan: Vec3::new(0.78, 0.66, 0.50),
+  ochre: Vec3::new(0.76, 0.58, 0.30),
+  blue_gray: Vec3::new(0.65, 0.72, 0.80),
+  haze: Vec3::new(0.12, 0.18, 0.24),
+
+  // planet_shader_rock
+  basalt: Vec3::new(0.12, 0.10, 0.09),
+  regolith: Vec3::new(0.38, 0.31, 0.22),
+  iron_oxide: Vec3::new(0.55, 0.32, 0.15),
+  crater_rim: Vec3::new(0.25, 0.22, 0.18),
+
+  // planet_shader_sun
+  photosphere: Vec3::new(1.0, 0.65, 0.18),
+  turbulence: Vec3::new(1.0, 0.8, 0.45),
+  corona: Vec3::new(1.0, 0.6, 0.25),
+
+  // ring_shader
+  ice: Vec3::new(0.82, 0.74, 0.62),
+  dust: Vec3::new(0.60, 0.53, 0.46),
+};
+
+// Decoded once instead of per fragment; the shaders light in linear space
+static LINEAR_PALETTE: LazyLock<Palette> = LazyLock::new(|| SRGB_PALETTE.map(|color| color.map(srgb_to_linear)));
+
+// The palette for the current gamma mode (see `srgb`)
+fn palette() -> &'static Palette {
+  if get_gamma_correct() { &LINEAR_PALETTE } else { &SRGB_PALETTE }
+}
+
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
   // Transform position
   let position = Vec4::new(
@@ -41,26 +238,12 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
     vertex.position.z,
     1.0
   );
-  let transformed = uniforms.model_matrix * position;
-
-  // Perform perspective division
-  let w = transformed.w;
-  let transformed_position = Vec3::new(
-    transformed.x / w,
-    transformed.y / w,
-    transformed.z / w
-  );
+  let world_position = uniforms.model_matrix * position;
+  let clip_position = uniforms.projection_matrix * uniforms.view_matrix * world_position;
+  let transformed_position = clip_to_screen(&clip_position, &uniforms.viewport_matrix);
 
-  // Transform normal
-
-  let model_mat3 = Mat3::new(
-    uniforms.model_matrix[0], uniforms.model_matrix[1], uniforms.model_matrix[2],
-    uniforms.model_matrix[4], uniforms.model_matrix[5], uniforms.model_matrix[6],
-    uniforms.model_matrix[8], uniforms.model_matrix[9], uniforms.model_matrix[10]
-  );
-  let normal_matrix = model_mat3.transpose().try_inverse().unwrap_or(Mat3::identity());
-
-  let transformed_normal = normal_matrix * vertex.normal;
+  // Transform normal (normal matrix is computed once per draw)
+  let transformed_normal = uniforms.normal_matrix * vertex.normal;
 
   // Create a new Vertex with transformed attributes
   Vertex {
@@ -68,18 +251,34 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
     normal: vertex.normal,
     tex_coords: vertex.tex_coords,
     color: vertex.color,
+    clip_position,
     transformed_position,
     transformed_normal,
+    world_position: world_position.xyz(),
   }
 }
 
+/// Perspective division followed by the viewport transform: screen pixels in x/y, depth in z.
+pub fn clip_to_screen(clip_position: &Vec4, viewport_matrix: &Mat4) -> Vec3 {
+  let w = clip_position.w;
+  let ndc_position = Vec4::new(
+    clip_position.x / w,
+    clip_position.y / w,
+    clip_position.z / w,
+    1.0
+  );
+  let screen_position = viewport_matrix * ndc_position;
+  Vec3::new(screen_position.x, screen_position.y, screen_position.z)
+}
+
 /// Procedural planet shader that returns an RGB color (Vec3) with components in [0,1].
 /// It combines several layers computed from position and normal only:
 /// 1. Vertical gradient (poles lighter, equator darker) using abs(normal.y)
 /// 2. Continental pattern: sin(pos.x*freq) * cos(pos.z*freq)
 /// 3. Small trig-based noise for micro-variation
 /// 4. Lambertian shading based on normal vs light direction
-pub fn planet_shader(pos: Vec3, normal: Vec3) -> Vec3 {
+#[allow(dead_code)]
+pub fn planet_shader(pos: Vec3, normal: Vec3, light_dir: Vec3) -> Vec3 {
   // normalize normal
   let n = normal.normalize();
 
@@ -99,10 +298,9 @@ pub fn planet_shader(pos: Vec3, normal: Vec3) -> Vec3 {
   // Layer 3: small trig-based noise (no external RNG)
 
   // Sci-fi palette: deep base, neon veins and bands
-  let base_ocean = Vec3::new(0.02, 0.05, 0.08);
-  let rock_dark = Vec3::new(0.10, 0.06, 0.16);
-  let neon_cyan = Vec3::new(0.0, 0.95, 0.85);
-  let neon_magenta = Vec3::new(0.95, 0.2, 0.85);
+  let palette = palette();
+  let (base_ocean, rock_dark) = (palette.ocean, palette.plates);
+  let (neon_cyan, neon_magenta) = (palette.neon_cyan, palette.neon_magenta);
 
   // Mix base planet - plates (land_mask) produce darker tech-plates
   let base = base_ocean * (1.0 - land_mask) + rock_dark * land_mask;
@@ -131,7 +329,6 @@ pub fn planet_shader(pos: Vec3, normal: Vec3) -> Vec3 {
   color *= 0.6 + 0.9 * gradient;
 
   // Lighting: basic lambert + specular-like highlight (sharp)
-  let light_dir = Vec3::new(0.6, 0.7, 0.3).normalize();
   let lambert = glm::dot(&n, &light_dir).max(0.0);
   let spec = lambert.powf(60.0) * 1.4; // tight bright highlights
   let ambient = 0.18;
@@ -142,28 +339,30 @@ pub fn planet_shader(pos: Vec3, normal: Vec3) -> Vec3 {
   let rim = (1.0 - glm::dot(&n, &Vec3::new(0.0, 0.0, 1.0))).powf(2.0);
   color += neon_cyan * (rim * 0.18);
 
-  // final clamp to [0,1]
-  Vec3::new(color.x.clamp(0.0, 1.0), color.y.clamp(0.0, 1.0), color.z.clamp(0.0, 1.0))
+  // Linear output; values above 1 are kept for the HDR buffer
+  color.map(|c| c.max(0.0))
 }
 
 /// Alternate planet shader variation (cooler palette)
-pub fn planet_shader_cool(pos: Vec3, normal: Vec3) -> Vec3 {
-  let mut c = planet_shader(pos, normal);
+#[allow(dead_code)]
+pub fn planet_shader_cool(pos: Vec3, normal: Vec3, light_dir: Vec3) -> Vec3 {
+  let mut c = planet_shader(pos, normal, light_dir);
   // shift towards blue/cyan
   c = Vec3::new(c.x * 0.6, c.y * 0.9, (c.z * 1.1).min(1.0));
   c
 }
 
 /// Alternate planet shader variation (warm palette)
-pub fn planet_shader_warm(pos: Vec3, normal: Vec3) -> Vec3 {
-  let mut c = planet_shader(pos, normal);
+#[allow(dead_code)]
+pub fn planet_shader_warm(pos: Vec3, normal: Vec3, light_dir: Vec3) -> Vec3 {
+  let mut c = planet_shader(pos, normal, light_dir);
   // shift towards warm/orange
   c = Vec3::new((c.x * 1.1).min(1.0), (c.y * 0.9).min(1.0), c.z * 0.6);
   c
 }
 
 /// Gaseous planet shader: banded clouds, flow-warp and soft lighting
-pub fn planet_shader_gas(pos: Vec3, normal: Vec3) -> Vec3 {
+pub fn planet_shader_gas(pos: Vec3, normal: Vec3, light_dir: Vec3) -> Vec3 {
   let n = normal.normalize();
 
   // Directions for isotropic trig-noise and domain warp
@@ -185,11 +384,9 @@ pub fn planet_shader_gas(pos: Vec3, normal: Vec3) -> Vec3 {
   let streak = ((glm::dot(&pos, &v2) * 6.0).sin().abs() * 0.25)
              + ((glm::dot(&pos, &v3) * 8.5).sin().abs() * 0.15);
 
-  // Softer gaseous palette (pastel creams/tans/ochres/blue-grays)
-  let cream     = Vec3::new(0.92, 0.88, 0.80);
-  let tan       = Vec3::new(0.78, 0.66, 0.50);
-  let ochre     = Vec3::new(0.76, 0.58, 0.30);
-  let blue_gray = Vec3::new(0.65, 0.72, 0.80);
+  // Softer gaseous palette (pastel creams/tans/ochres/blue-grays), authored in sRGB
+  let palette = palette();
+  let (cream, tan, ochre, blue_gray) = (palette.cream, palette.tan, palette.ochre, palette.blue_gray);
 
   // Two base band families and a slow alternation between families
   let band_family_a = cream * (1.0 - band_val) + tan * band_val;       // light bands
@@ -199,7 +396,7 @@ pub fn planet_shader_gas(pos: Vec3, normal: Vec3) -> Vec3 {
 
   let mut color = band_family_a * (1.0 - family_mix) + band_family_b * family_mix;
   // Add gentle streak modulation
-  color = color * (1.0 + 0.18 * streak);
+  color *= 1.0 + 0.18 * streak;
 
   // Subtle turbulence to break uniformity
   let turb = ((glm::dot(&pos, &v1) * 3.1).sin().abs() * 0.12)
@@ -207,7 +404,6 @@ pub fn planet_shader_gas(pos: Vec3, normal: Vec3) -> Vec3 {
   color *= 1.0 + turb;
 
   // Soft lighting (clouds): mostly diffuse, low specular
-  let light_dir = Vec3::new(0.6, 0.7, 0.3).normalize();
   let lambert = glm::dot(&n, &light_dir).max(0.0);
   let spec = lambert.powf(8.0) * 0.05;
   let ambient = 0.35;
@@ -216,13 +412,13 @@ pub fn planet_shader_gas(pos: Vec3, normal: Vec3) -> Vec3 {
 
   // Gentle rim light to suggest atmospheric scattering
   let rim = (1.0 - glm::dot(&n, &Vec3::new(0.0, 0.0, 1.0))).powf(2.2);
-  color += Vec3::new(0.12, 0.18, 0.24) * (rim * 0.18);
+  color += palette.haze * (rim * 0.18);
 
-  Vec3::new(color.x.clamp(0.0, 1.0), color.y.clamp(0.0, 1.0), color.z.clamp(0.0, 1.0))
+  color.map(|c| c.max(0.0))
 }
 
 /// Rocky planet shader: stratified rock, regolith and cracks with lambertian lighting
-pub fn planet_shader_rock(pos: Vec3, normal: Vec3) -> Vec3 {
+pub fn planet_shader_rock(pos: Vec3, normal: Vec3, light_dir: Vec3) -> Vec3 {
   let n = normal.normalize();
   let seed_vec = noise_seed_vec3();
   // Seeded position for noise domain warp (keeps shape, changes patterns)
@@ -254,10 +450,9 @@ pub fn planet_shader_rock(pos: Vec3, normal: Vec3) -> Vec3 {
   let crack_b = ((glm::dot(&p, &v2) * 7.1).sin() * (glm::dot(&p, &v3) * 7.4).sin()).abs();
   let cracks = (crack_a.min(crack_b)).powf(12.0).clamp(0.0, 1.0);
 
-  // Base rocky palette
-  let basalt = Vec3::new(0.12, 0.10, 0.09);
-  let regolith = Vec3::new(0.38, 0.31, 0.22);
-  let iron_oxide = Vec3::new(0.55, 0.32, 0.15);
+  // Base rocky palette, authored in sRGB
+  let palette = palette();
+  let (basalt, regolith, iron_oxide) = (palette.basalt, palette.regolith, palette.iron_oxide);
 
   // Blend materials (add slope-based dust accumulation)
   let up = if pos.magnitude() > 0.0 { pos / pos.magnitude() } else { Vec3::new(0.0, 1.0, 0.0) };
@@ -287,17 +482,17 @@ pub fn planet_shader_rock(pos: Vec3, normal: Vec3) -> Vec3 {
   // Hash helpers to get pseudo-random in [0,1)
   let h1 = {
     let d = glm::dot(&cell, &Vec3::new(12.9898, 78.233, 37.719)) + seed_vec.x * 97.0;
-    let s = (d).sin() * 43758.5453;
+    let s = (d).sin() * 43758.547;
     s - s.floor()
   };
   let h2 = {
     let d = glm::dot(&cell, &Vec3::new(93.989, 67.345, 24.123)) + seed_vec.y * 73.0;
-    let s = (d).sin() * 12753.5453;
+    let s = (d).sin() * 12753.545;
     s - s.floor()
   };
   let h3 = {
     let d = glm::dot(&cell, &Vec3::new(53.786, 12.345, 91.532)) + seed_vec.z * 59.0;
-    let s = (d).sin() * 31837.1234;
+    let s = (d).sin() * 31837.123;
     s - s.floor()
   };
   // Only place a crater in some cells
@@ -314,27 +509,170 @@ pub fn planet_shader_rock(pos: Vec3, normal: Vec3) -> Vec3 {
     let crater_dark = bowl * 0.22;
     let rim_bright = rim * 0.08;
     color *= 1.0 - crater_dark;
-    color += Vec3::new(0.25, 0.22, 0.18) * rim_bright; // slightly warmer rim
+    color += palette.crater_rim * rim_bright; // slightly warmer rim
   }
 
   // Lighting: rough rock, low specular
-  let light_dir = Vec3::new(0.6, 0.7, 0.3).normalize();
   let lambert = glm::dot(&n, &light_dir).max(0.0);
   let spec = lambert.powf(12.0) * 0.15; // rough highlight
   let ambient = 0.22;
   let lit = ambient + 0.95 * lambert + spec;
   color *= lit;
 
-  Vec3::new(color.x.clamp(0.0, 1.0), color.y.clamp(0.0, 1.0), color.z.clamp(0.0, 1.0))
+  color.map(|c| c.max(0.0))
 }
 
-/// Generic shade entry — dispatches to the selected shader variant.
-pub fn shade(pos: Vec3, normal: Vec3) -> Vec3 {
-  match get_shader_index() {
-    0 => planet_shader_gas(pos, normal),
-    1 => planet_shader_rock(pos, normal),
-    2 => planet_shader_sun(pos, normal),
-    _ => planet_shader_gas(pos, normal),
+const POSITION_PERIOD: f32 = 32.0;
+// Brightness of the farthest surface, kept above the black background
+const LINEAR_DEPTH_FLOOR: f32 = 0.15;
+
+/// Debug view color for an interpolated fragment. `weights` are its perspective-correct
+/// barycentrics. Values are shown as-is: they are decoded here so the sRGB encode on
+/// output gives them back unchanged.
+pub fn debug_shade(view: DebugView, fragment: &Vertex, weights: (f32, f32, f32), primitive_id: u32) -> Vec3 {
+  let color = match view {
+    DebugView::Off => Vec3::zeros(),
+    DebugView::Normals => fragment.transformed_normal.normalize() * 0.5 + Vec3::new(0.5, 0.5, 0.5),
+    DebugView::Position => fragment.position.map(|c| (c / POSITION_PERIOD).rem_euclid(1.0)),
+    DebugView::LinearDepth => {
+      // clip w is the distance along the view axis
+      let (near, far) = get_depth_range();
+      let t = ((fragment.clip_position.w - near) / (far - near).max(1e-6)).clamp(0.0, 1.0);
+      let depth = 1.0 - (1.0 - LINEAR_DEPTH_FLOOR) * t;
+      Vec3::new(depth, depth, depth)
+    }
+    DebugView::Barycentrics => Vec3::new(weights.0, weights.1, weights.2),
+    DebugView::TexCoords => Vec3::new(fragment.tex_coords.x.fract(), fragment.tex_coords.y.fract(), 0.0),
+    DebugView::TriangleId => {
+      // Spread consecutive ids over the hue circle (golden ratio steps)
+      let hue = (primitive_id as f32 * 0.618_034).fract();
+      let channel = |offset: f32| 0.5 + 0.5 * (std::f32::consts::TAU * (hue + offset)).cos();
+      Vec3::new(channel(0.0), channel(2.0 / 3.0), channel(1.0 / 3.0))
+    }
+  };
+  srgb(color.x, color.y, color.z)
+}
+
+/// What a fragment shader produces. `emissive` is the light the surface gives off by
+/// itself; only that part feeds the bloom, so lit planets don't glow. `alpha` below 1
+/// blends the fragment over what is already drawn.
+#[derive(Clone, Copy, Debug)]
+pub struct ShaderOutput {
+  pub color: Vec3,
+  pub emissive: Vec3,
+  pub alpha: f32,
+}
+
+impl ShaderOutput {
+  pub fn lit(color: Vec3) -> Self {
+    ShaderOutput { color, emissive: Vec3::zeros(), alpha: 1.0 }
+  }
+
+  pub fn emissive(color: Vec3) -> Self {
+    ShaderOutput { color, emissive: color, alpha: 1.0 }
+  }
+}
+
+/// Generic shade entry — dispatches to the material's shader, or to the override if one is set.
+/// Surfaces are lit from `sun`, the light's world position. `rings` is the ring system of
+/// the planet being drawn, which the planet and its rings use to shadow each other.
+pub fn shade(material: Material, fragment: &Vertex, sun: &Vec3, rings: Option<&RingShadow>) -> ShaderOutput {
+  let material = if material.is_transparent() { material } else { get_material_override().unwrap_or(material) };
+  let (pos, normal) = (fragment.position, fragment.transformed_normal.normalize());
+  // The sun is close to the planets, so the direction changes across the scene
+  let light_dir = (sun - fragment.world_position).try_normalize(1e-6).unwrap_or_else(|| Vec3::new(0.0, 1.0, 0.0));
+  let mut output = match material {
+    Material::Gas => ShaderOutput::lit(planet_shader_gas(pos, normal, light_dir)),
+    Material::Rock => ShaderOutput::lit(planet_shader_rock(pos, normal, light_dir)),
+    Material::Sun => return ShaderOutput::emissive(planet_shader_sun(pos, normal)),
+    Material::Ring => ring_shader(fragment.tex_coords.x, normal, light_dir),
+  };
+
+  if let Some(rings) = rings {
+    let light = if material == Material::Ring {
+      rings.planet_transmittance(&fragment.world_position, &light_dir)
+    } else {
+      rings.ring_transmittance(&fragment.world_position, &light_dir)
+    };
+    output.color *= SHADOW_FLOOR + (1.0 - SHADOW_FLOOR) * light;
+  }
+  output
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+  let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+  t * t * (3.0 - 2.0 * t)
+}
+
+// Share of the lighting left in full shadow, standing in for the ambient term
+const SHADOW_FLOOR: f32 = 0.2;
+
+/// Opacity of the rings at `u`, 0 at the inner edge and 1 at the outer edge: broad
+/// bands made of fine ringlets, a wide and a narrow gap, and edges that thin out.
+pub fn ring_density(u: f32) -> f32 {
+  if !(0.0..=1.0).contains(&u) {
+    return 0.0;
+  }
+  let bands = 0.6 + 0.25 * (u * 11.0).sin() + 0.15 * (u * 29.0 + 1.3).sin();
+  let ringlets = 0.85 + 0.15 * (u * 173.0).sin();
+  let gap = |center: f32, width: f32| smoothstep(width * 0.5, width, (u - center).abs());
+  let edges = smoothstep(0.0, 0.12, u) * (1.0 - smoothstep(0.93, 1.0, u));
+  (0.9 * bands * ringlets * gap(0.64, 0.05) * gap(0.87, 0.012) * edges).clamp(0.0, 1.0)
+}
+
+/// Planetary rings, `u` being the radial texture coordinate. Dust and ice are lit from
+/// either side; the side away from the light only gets what passes through.
+pub fn ring_shader(u: f32, normal: Vec3, light_dir: Vec3) -> ShaderOutput {
+  let density = ring_density(u);
+  let tint = (u * 23.0).sin() * 0.5 + 0.5;
+  let palette = palette();
+  let base = palette.ice * (1.0 - tint) + palette.dust * tint;
+
+  let facing = glm::dot(&normal, &light_dir);
+  let diffuse = if facing > 0.0 { facing } else { -facing * (1.0 - density) * 0.6 };
+  let ambient = 0.12;
+  ShaderOutput { color: base * (ambient + 0.9 * diffuse), emissive: Vec3::zeros(), alpha: density }
+}
+
+/// A planet's ring system in world space, for the shadows the planet and the rings
+/// cast on each other.
+#[derive(Clone, Copy, Debug)]
+pub struct RingShadow {
+  pub center: Vec3,
+  // Normal of the ring plane
+  pub normal: Vec3,
+  pub planet_radius: f32,
+  pub inner_radius: f32,
+  pub outer_radius: f32,
+}
+
+impl RingShadow {
+  /// Light reaching `point` on the rings past the planet, `light` pointing from `point`
+  /// towards the sun: 0 where the planet hides it, with a narrow soft edge.
+  pub fn planet_transmittance(&self, point: &Vec3, light: &Vec3) -> f32 {
+    let offset = point - self.center;
+    let along = glm::dot(&offset, light);
+    // Nearer the sun than the planet is
+    if along >= 0.0 {
+      return 1.0;
+    }
+    let from_axis = (offset - light * along).magnitude();
+    smoothstep(self.planet_radius * 0.97, self.planet_radius * 1.03, from_axis)
+  }
+
+  /// Light reaching `point` on the planet through the rings: where the ray towards the
+  /// light crosses the ring plane, whatever the rings let through at that radius.
+  pub fn ring_transmittance(&self, point: &Vec3, light: &Vec3) -> f32 {
+    let facing = glm::dot(light, &self.normal);
+    if facing.abs() < 1e-4 {
+      return 1.0;
+    }
+    let distance = glm::dot(&(self.center - point), &self.normal) / facing;
+    if distance <= 0.0 {
+      return 1.0;
+    }
+    let radius = (point + light * distance - self.center).magnitude();
+    1.0 - ring_density((radius - self.inner_radius) / (self.outer_radius - self.inner_radius))
   }
 }
 
@@ -344,7 +682,8 @@ pub fn planet_shader_sun(pos: Vec3, normal: Vec3) -> Vec3 {
   let n = normal.normalize();
 
   // Uniform emissive base: warm orange, slightly less bright overall
-  let base = Vec3::new(1.0, 0.65, 0.18);
+  let palette = palette();
+  let base = palette.photosphere;
   let mut color = base * 0.85; // tone down brightness a bit
 
   // Isotropic turbulence (replaces angular rays to avoid vertical lines)
@@ -356,7 +695,7 @@ pub fn planet_shader_sun(pos: Vec3, normal: Vec3) -> Vec3 {
   let n2 = (glm::dot(&p, &v2) * 1.6).sin();
   let n3 = (glm::dot(&p, &v3) * 2.3).sin();
   let turb = (n1.abs() * 0.5 + n2.abs() * 0.3 + n3.abs() * 0.2).clamp(0.0, 1.0);
-  color += Vec3::new(1.0, 0.8, 0.45) * (turb * 0.25);
+  color += palette.turbulence * (turb * 0.25);
 
   // Gentle additive flicker (kept subtle)
   let flicker = ((pos.x * 0.12).sin() * (pos.y * 0.13).cos() * (pos.z * 0.11).sin() * 0.10 + 0.10).max(0.0);
@@ -386,9 +725,73 @@ pub fn planet_shader_sun(pos: Vec3, normal: Vec3) -> Vec3 {
 
   // Add a soft rim/glow using normal vs view axis (additive only)
   let rim = (1.0 - glm::dot(&n, &Vec3::new(0.0, 0.0, 1.0))).powf(3.0);
-  color += Vec3::new(1.0, 0.6, 0.25) * (rim * 0.2); // keep rim subtler for "less bright"
+  color += palette.corona * (rim * 0.2); // keep rim subtler for "less bright"
 
-  // Tone mapping / clamp
-  Vec3::new(color.x.min(1.0), color.y.min(1.0), color.z.min(1.0))
+  // Emissive highlights go above 1; the framebuffer tone maps them when rendering HDR
+  color.map(|c| c.max(0.0))
 }
 
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // A planet of radius 1 at the origin with rings in the XZ plane
+  fn rings() -> RingShadow {
+    RingShadow {
+      center: Vec3::zeros(),
+      normal: Vec3::new(0.0, 1.0, 0.0),
+      planet_radius: 1.0,
+      inner_radius: 1.5,
+      outer_radius: 2.5,
+    }
+  }
+
+  // The point in the ring plane at fraction `u` across the rings, in direction `angle`
+  fn ring_point(rings: &RingShadow, u: f32, angle: f32) -> Vec3 {
+    let radius = rings.inner_radius + u * (rings.outer_radius - rings.inner_radius);
+    Vec3::new(radius * angle.cos(), 0.0, radius * angle.sin())
+  }
+
+  // The sun, a few planet radii away above the ring plane
+  const SUN: Vec3 = Vec3::new(4.0, 5.0, 2.0);
+
+  // Direction from `point` towards the sun
+  fn light_at(point: &Vec3) -> Vec3 {
+    (SUN - point).normalize()
+  }
+
+  #[test]
+  fn planet_shadows_the_rings_behind_it() {
+    let rings = rings();
+    let away = -light_at(&rings.center);
+    let transmittance = |point: Vec3| rings.planet_transmittance(&point, &light_at(&point));
+
+    assert_eq!(transmittance(rings.center + away * 3.0), 0.0);
+    assert_eq!(transmittance(rings.center - away * 3.0), 1.0);
+    // Behind the planet, but well off the shadow axis
+    let side = away.cross(&rings.normal).normalize();
+    assert_eq!(transmittance(side * 2.0 + away * 3.0), 1.0);
+  }
+
+  #[test]
+  fn rings_shadow_the_planet_except_through_gaps() {
+    let rings = rings();
+    let transmittance = |point: Vec3| rings.ring_transmittance(&point, &light_at(&point));
+    // Under the ring plane, on the line from the sun through the ring at `u`
+    let below = |u: f32| {
+      let point = ring_point(&rings, u, 2.0);
+      point - light_at(&point)
+    };
+
+    assert_eq!(ring_density(0.64), 0.0);
+    assert_eq!(transmittance(below(0.64)), 1.0);
+    let through_band = transmittance(below(0.3));
+    assert!((through_band - (1.0 - ring_density(0.3))).abs() < 1e-4);
+    assert!(through_band < 0.9, "a band lets {through_band} through");
+    // Past the outer edge, and above the rings (the light doesn't cross them)
+    assert_eq!(transmittance(below(1.5)), 1.0);
+    let above = ring_point(&rings, 0.3, 2.0) + Vec3::new(0.0, 0.5, 0.0);
+    assert_eq!(transmittance(above), 1.0);
+  }
+}
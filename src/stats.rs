@@ -0,0 +1,30 @@
+use std::ops::AddAssign;
+
+/// Per-frame pipeline counters, summed over every tile.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+  // Mesh triangles sent down the pipeline
+  pub triangles_submitted: u64,
+  // Triangles dropped before rasterization, by face culling or outside the frustum
+  pub triangles_culled: u64,
+  // Covered pixels that reached the depth test of the shading pass
+  pub fragments: u64,
+  // Times the procedural shader actually ran
+  pub shader_invocations: u64,
+}
+
+impl RenderStats {
+  // Fragments that were rejected by the early depth test instead of being shaded
+  pub fn shader_invocations_saved(&self) -> u64 {
+    self.fragments - self.shader_invocations
+  }
+}
+
+impl AddAssign for RenderStats {
+  fn add_assign(&mut self, other: RenderStats) {
+    self.triangles_submitted += other.triangles_submitted;
+    self.triangles_culled += other.triangles_culled;
+    self.fragments += other.fragments;
+    self.shader_invocations += other.shader_invocations;
+  }
+}
@@ -0,0 +1,169 @@
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use crate::framebuffer::{Framebuffer, FramebufferBand, DepthPass};
+use crate::stats::RenderStats;
+use crate::triangle::{triangle, calculate_bounding_box, Primitive};
+use crate::vertex::Vertex;
+
+pub const TILE_SIZE: usize = 32;
+
+static DEPTH_PREPASS: AtomicBool = AtomicBool::new(false);
+
+// With the pre-pass on, every tile first resolves its depth and then shades each
+// visible pixel exactly once, instead of shading whatever is closest so far.
+pub fn set_depth_prepass(enabled: bool) {
+  DEPTH_PREPASS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn get_depth_prepass() -> bool {
+  DEPTH_PREPASS.load(Ordering::Relaxed)
+}
+
+pub fn worker_count() -> usize {
+  static WORKERS: OnceLock<usize> = OnceLock::new();
+  *WORKERS.get_or_init(|| thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+}
+
+/// Bins triangles into TILE_SIZE x TILE_SIZE screen tiles and rasterizes the tiles on
+/// `threads` workers. `primitives[i]` holds the per-triangle state of `triangles[i]`.
+/// Each row of tiles owns a disjoint band of the framebuffer and every tile walks its
+/// triangles in submission order, so the output is identical to `threads == 1`.
+pub fn rasterize(framebuffer: &mut Framebuffer, triangles: &[[Vertex; 3]], primitives: &[Primitive], threads: usize) -> RenderStats {
+  let tiles_x = framebuffer.width.div_ceil(TILE_SIZE);
+  let tiles_y = framebuffer.height.div_ceil(TILE_SIZE);
+  let bins = bin_triangles(triangles, framebuffer.width, framebuffer.height, tiles_x, tiles_y);
+
+  let prepass = get_depth_prepass();
+  let bands = framebuffer.bands_mut(TILE_SIZE);
+  let mut stats = RenderStats::default();
+  if threads <= 1 {
+    for band in bands {
+      stats += rasterize_band(band, triangles, primitives, &bins, tiles_x, prepass);
+    }
+    return stats;
+  }
+
+  // Workers pull tile rows from a shared queue so busy rows (the middle of a planet)
+  // don't leave the other threads idle.
+  let queue = Mutex::new(bands);
+  thread::scope(|scope| {
+    let workers: Vec<_> = (0..threads.min(tiles_y))
+      .map(|_| scope.spawn(|| {
+        let mut stats = RenderStats::default();
+        loop {
+          let band = queue.lock().unwrap().pop();
+          match band {
+            Some(band) => stats += rasterize_band(band, triangles, primitives, &bins, tiles_x, prepass),
+            None => break stats,
+          }
+        }
+      }))
+      .collect();
+    for worker in workers {
+      stats += worker.join().unwrap();
+    }
+  });
+  stats
+}
+
+// For every tile, the indices of the triangles whose bounding box touches it.
+fn bin_triangles(triangles: &[[Vertex; 3]], width: usize, height: usize, tiles_x: usize, tiles_y: usize) -> Vec<Vec<usize>> {
+  let mut bins = vec![Vec::new(); tiles_x * tiles_y];
+
+  for (i, tri) in triangles.iter().enumerate() {
+    let (min_x, min_y, max_x, max_y) = calculate_bounding_box(
+      &tri[0].transformed_position,
+      &tri[1].transformed_position,
+      &tri[2].transformed_position,
+    );
+    let (min_x, min_y) = (min_x.max(0), min_y.max(0));
+    let (max_x, max_y) = (max_x.min(width as i32 - 1), max_y.min(height as i32 - 1));
+    if min_x > max_x || min_y > max_y {
+      continue;
+    }
+
+    for ty in (min_y as usize / TILE_SIZE)..=(max_y as usize / TILE_SIZE) {
+      for tx in (min_x as usize / TILE_SIZE)..=(max_x as usize / TILE_SIZE) {
+        bins[ty * tiles_x + tx].push(i);
+      }
+    }
+  }
+
+  bins
+}
+
+fn rasterize_band(mut band: FramebufferBand, triangles: &[[Vertex; 3]], primitives: &[Primitive], bins: &[Vec<usize>], tiles_x: usize, prepass: bool) -> RenderStats {
+  let mut stats = RenderStats::default();
+  let ty = band.y / TILE_SIZE;
+  let (min_y, max_y) = (band.y as i32, (band.y + band.height) as i32 - 1);
+
+  for tx in 0..tiles_x {
+    let min_x = (tx * TILE_SIZE) as i32;
+    let max_x = ((tx + 1) * TILE_SIZE).min(band.width) as i32 - 1;
+    let bounds = (min_x, min_y, max_x, max_y);
+    let bin = &bins[ty * tiles_x + tx];
+
+    if prepass {
+      band.pass = DepthPass::PrePass;
+      // Transparent surfaces don't hide anything behind them, so they stay out of the pre-pass
+      for &i in bin.iter().filter(|&&i| !primitives[i].material.is_transparent()) {
+        triangle(&triangles[i][0], &triangles[i][1], &triangles[i][2], primitives[i], bounds, &mut band);
+      }
+      band.pass = DepthPass::Shading;
+    }
+    for &i in bin {
+      stats += triangle(&triangles[i][0], &triangles[i][1], &triangles[i][2], primitives[i], bounds, &mut band);
+    }
+  }
+
+  stats
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nalgebra_glm::{Vec2, Vec3};
+  use crate::shaders::Material;
+
+  fn screen_vertex(x: f32, y: f32, z: f32) -> Vertex {
+    let position = Vec3::new(x, y, z);
+    Vertex::new(position, Vec3::new(0.0, 0.0, 1.0), Vec2::new(x / 200.0, y / 150.0))
+  }
+
+  #[test]
+  fn threaded_output_matches_single_threaded() {
+    // Overlapping triangles that straddle tile boundaries, at crossing depths, with
+    // translucent ones whose blending depends on submission order
+    let mut triangles = Vec::new();
+    let mut primitives = Vec::new();
+    let materials = [Material::Rock, Material::Gas, Material::Ring, Material::Sun];
+    for i in 0..24 {
+      let (x, y) = ((i * 37 % 150) as f32, (i * 23 % 100) as f32);
+      let z = 0.2 + 0.6 * (i % 5) as f32 / 4.0;
+      triangles.push([
+        screen_vertex(x, y, z),
+        screen_vertex(x + 70.0, y + 15.0, 1.0 - z),
+        screen_vertex(x + 20.0, y + 60.0, 0.5),
+      ]);
+      primitives.push(Primitive {
+        id: i as u32,
+        material: materials[i % materials.len()],
+        sun: Vec3::new(0.0, 0.0, 10.0),
+        rings: None,
+      });
+    }
+
+    let render = |threads: usize| {
+      let mut framebuffer = Framebuffer::new(200, 150);
+      rasterize(&mut framebuffer, &triangles, &primitives, threads);
+      (framebuffer.buffer, framebuffer.zbuffer)
+    };
+    let (single, single_depth) = render(1);
+    let (threaded, threaded_depth) = render(4);
+
+    assert!(single.iter().any(|&pixel| pixel != 0), "nothing was drawn");
+    assert!(single == threaded, "color buffers differ");
+    assert!(single_depth == threaded_depth, "depth buffers differ");
+  }
+}
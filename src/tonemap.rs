@@ -0,0 +1,41 @@
+use nalgebra_glm::Vec3;
+
+/// Operator that maps linear HDR color (any value >= 0) into the displayable [0,1] range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+  // c / (1 + c): never clips, but flattens highlights
+  Reinhard,
+  // Narkowicz's fit of the ACES filmic curve: contrasty, slightly desaturated highlights
+  Aces,
+  // 1 - e^(-c): photographic exposure curve
+  Exposure,
+}
+
+impl ToneMap {
+  pub fn next(self) -> Self {
+    match self {
+      ToneMap::Reinhard => ToneMap::Aces,
+      ToneMap::Aces => ToneMap::Exposure,
+      ToneMap::Exposure => ToneMap::Reinhard,
+    }
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      ToneMap::Reinhard => "Reinhard",
+      ToneMap::Aces => "ACES",
+      ToneMap::Exposure => "exposure",
+    }
+  }
+
+  /// Scales `color` by `exposure` and maps it into [0,1].
+  pub fn apply(self, color: Vec3, exposure: f32) -> Vec3 {
+    let c = color * exposure;
+    let mapped = match self {
+      ToneMap::Reinhard => c.component_div(&c.add_scalar(1.0)),
+      ToneMap::Aces => c.map(|x| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)),
+      ToneMap::Exposure => c.map(|x| 1.0 - (-x).exp()),
+    };
+    mapped.map(|x| x.clamp(0.0, 1.0))
+  }
+}
@@ -1,68 +1,357 @@
 use nalgebra_glm::Vec3;
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
-use crate::line::line;
-use crate::color::Color;
+use crate::line::{line, LineStyle};
+use crate::stats::RenderStats;
+use crate::shaders::{shade, debug_shade, get_debug_view, DebugView, Material, RingShadow, ShaderOutput};
+use std::sync::atomic::{AtomicU8, Ordering};
 
-pub fn _triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+  None,
+  Back,
+  Front,
+}
+
+impl CullMode {
+  pub fn next(self) -> Self {
+    match self {
+      CullMode::None => CullMode::Back,
+      CullMode::Back => CullMode::Front,
+      CullMode::Front => CullMode::None,
+    }
+  }
+}
+
+static CULL_MODE: AtomicU8 = AtomicU8::new(CullMode::Back as u8);
+
+pub fn set_cull_mode(mode: CullMode) {
+  CULL_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn get_cull_mode() -> CullMode {
+  match CULL_MODE.load(Ordering::Relaxed) {
+    0 => CullMode::None,
+    1 => CullMode::Back,
+    _ => CullMode::Front,
+  }
+}
+
+/// What `render` draws: filled triangles, their edges, or the edges on top of the
+/// filled triangles (hidden-line, since the edges are depth-tested against the fill).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+  Solid,
+  Wireframe,
+  SolidWireframe,
+}
+
+impl RenderMode {
+  pub fn next(self) -> Self {
+    match self {
+      RenderMode::Solid => RenderMode::Wireframe,
+      RenderMode::Wireframe => RenderMode::SolidWireframe,
+      RenderMode::SolidWireframe => RenderMode::Solid,
+    }
+  }
+}
+
+static RENDER_MODE: AtomicU8 = AtomicU8::new(RenderMode::Solid as u8);
+
+pub fn set_render_mode(mode: RenderMode) {
+  RENDER_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn get_render_mode() -> RenderMode {
+  match RENDER_MODE.load(Ordering::Relaxed) {
+    0 => RenderMode::Solid,
+    1 => RenderMode::Wireframe,
+    _ => RenderMode::SolidWireframe,
+  }
+}
+
+/// Meshes are counter-clockwise in world space (see `Obj::load`); after the viewport's
+/// Y flip a front face has a positive signed area on screen. Zero-area triangles are
+/// dropped by either culling mode.
+pub fn is_culled(v1: &Vertex, v2: &Vertex, v3: &Vertex, mode: CullMode) -> bool {
+  let area = edge_function(&v1.transformed_position, &v2.transformed_position, &v3.transformed_position);
+  match mode {
+    CullMode::None => false,
+    CullMode::Back => area <= 0.0,
+    CullMode::Front => area >= 0.0,
+  }
+}
+
+/// Fragments along the three edges of a triangle, for wireframe rendering.
+pub fn triangle_outline(v1: &Vertex, v2: &Vertex, v3: &Vertex, style: &LineStyle, width: usize, height: usize) -> Vec<Fragment> {
   let mut fragments = Vec::new();
 
   // Draw the three sides of the triangle
-  fragments.extend(line(v1, v2));
-  fragments.extend(line(v2, v3));
-  fragments.extend(line(v3, v1));
+  fragments.extend(line(v1, v2, style, width, height));
+  fragments.extend(line(v2, v3, style, width, height));
+  fragments.extend(line(v3, v1, style, width, height));
 
   fragments
 }
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
-  let mut fragments = Vec::new();
-  let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+/// Receives the fragments of a triangle as they are rasterized. The depth test runs
+/// before shading, so occluded pixels never pay for the procedural shader, and no
+/// per-triangle fragment list is ever allocated.
+///
+/// With multisampling, coverage and depth are handled per sample while the shader
+/// still runs once per pixel; its color is written to every sample that passed.
+pub trait FragmentSink {
+  fn sample_count(&self) -> usize;
+  fn depth_test(&mut self, x: usize, y: usize, sample: usize, depth: f32) -> bool;
+  fn write(&mut self, fragment: &Fragment, sample: usize);
 
-  let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
+  // Per-pixel cost counters for the heatmap; sinks that don't keep them ignore these
+  fn count_fragment(&mut self, _x: usize, _y: usize) {}
+  fn count_shader_invocation(&mut self, _x: usize, _y: usize) {}
+}
 
-  // Lighting is handled inside the procedural shader (planet_shader).
+pub const MAX_SAMPLES: usize = 8;
 
-  let triangle_area = edge_function(&a, &b, &c);
+// Standard MSAA sample positions in 1/16 pixel units relative to the pixel center
+// (the usual D3D patterns), so edges get evenly spread coverage levels.
+fn sample_pattern(samples: usize) -> &'static [(i64, i64)] {
+  match samples {
+    2 => &[(4, 4), (-4, -4)],
+    4 => &[(-2, -6), (6, -2), (-6, 2), (2, 6)],
+    8 => &[(1, -3), (-1, 3), (5, 1), (-3, -5), (-5, 5), (-7, -1), (3, 7), (7, -7)],
+    _ => &[(0, 0)],
+  }
+}
+
+// Screen positions are snapped to a 1/256 pixel grid and edge functions are evaluated
+// exactly in integers, so triangles sharing an edge agree on every pixel along it.
+const SUBPIXEL_BITS: u32 = 8;
+const SUBPIXEL_ONE: i64 = 1 << SUBPIXEL_BITS;
+
+type FixedPoint = (i64, i64);
+
+fn snap(v: &Vec3) -> FixedPoint {
+  let scale = SUBPIXEL_ONE as f32;
+  ((v.x * scale).round() as i64, (v.y * scale).round() as i64)
+}
+
+fn pixel_center(x: i32, y: i32) -> FixedPoint {
+  (x as i64 * SUBPIXEL_ONE + SUBPIXEL_ONE / 2, y as i64 * SUBPIXEL_ONE + SUBPIXEL_ONE / 2)
+}
+
+fn edge_function_fixed(a: &FixedPoint, b: &FixedPoint, c: &FixedPoint) -> i64 {
+  (c.0 - a.0) * (b.1 - a.1) - (c.1 - a.1) * (b.0 - a.0)
+}
+
+// Top-left fill rule for a positive-area triangle (y grows downwards): a pixel center
+// exactly on an edge is only covered when that edge is a left edge or a flat top edge.
+fn is_top_left(a: &FixedPoint, b: &FixedPoint) -> bool {
+  let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+  dy > 0 || (dy == 0 && dx < 0)
+}
+
+// Pixels are tested in horizontal blocks of this many lanes at once
+const BLOCK_WIDTH: usize = 4;
+
+// An edge function set up for incremental stepping: moving one pixel right adds
+// `step_x`, moving one pixel down adds `step_y`. Integer math keeps the stepped
+// values identical to evaluating the edge function from scratch.
+struct EdgeStepper {
+  row: i64,
+  step_x: i64,
+  step_y: i64,
+  bias: i64,
+  // Offset of each MSAA sample's edge value from the pixel center's
+  sample_offsets: [i64; MAX_SAMPLES],
+}
+
+impl EdgeStepper {
+  fn new(a: &FixedPoint, b: &FixedPoint, origin: &FixedPoint, pattern: &[(i64, i64)]) -> Self {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let mut sample_offsets = [0; MAX_SAMPLES];
+    for (offset, &(sx, sy)) in sample_offsets.iter_mut().zip(pattern) {
+      let (sx, sy) = (sx * SUBPIXEL_ONE / 16, sy * SUBPIXEL_ONE / 16);
+      *offset = sx * dy - sy * dx;
+    }
+    EdgeStepper {
+      row: edge_function_fixed(a, b, origin),
+      step_x: dy * SUBPIXEL_ONE,
+      step_y: -dx * SUBPIXEL_ONE,
+      // Edges that don't own their boundary need a strictly positive edge value
+      bias: if is_top_left(a, b) { 0 } else { 1 },
+      sample_offsets,
+    }
+  }
+
+  // Edge values for the BLOCK_WIDTH pixels starting `offset` pixels into the current row
+  fn block(&self, offset: i64) -> [i64; BLOCK_WIDTH] {
+    let start = self.row + offset * self.step_x;
+    std::array::from_fn(|lane| start + lane as i64 * self.step_x)
+  }
+}
+
+/// Per-triangle state that is the same for all three vertices.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Primitive {
+  // Source triangle, for the triangle-ID debug view
+  pub id: u32,
+  pub material: Material,
+  // World position of the sun, which lights everything else
+  pub sun: Vec3,
+  // Ring system of the planet the triangle belongs to, for ring / planet shadows
+  pub rings: Option<RingShadow>,
+}
+
+/// Rasterizes a triangle into `sink`, restricted to the inclusive pixel rectangle
+/// `bounds` given as (min_x, min_y, max_x, max_y), e.g. the screen tile being shaded.
+/// Either winding is accepted; zero-area triangles produce no fragments.
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, primitive: Primitive, bounds: (i32, i32, i32, i32), sink: &mut impl FragmentSink) -> RenderStats {
+  let mut stats = RenderStats::default();
+  let (mut v2, mut v3) = (v2, v3);
+  let a = snap(&v1.transformed_position);
+  let mut b = snap(&v2.transformed_position);
+  let mut c = snap(&v3.transformed_position);
+
+  let mut triangle_area = edge_function_fixed(&a, &b, &c);
+  if triangle_area == 0 {
+    return stats;
+  }
+  // Reorder clockwise triangles so the inside is always where all edges are positive
+  if triangle_area < 0 {
+    std::mem::swap(&mut v2, &mut v3);
+    std::mem::swap(&mut b, &mut c);
+    triangle_area = -triangle_area;
+  }
+
+  let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&v1.transformed_position, &v2.transformed_position, &v3.transformed_position);
+  let (min_x, min_y) = (min_x.max(bounds.0), min_y.max(bounds.1));
+  let (max_x, max_y) = (max_x.min(bounds.2), max_y.min(bounds.3));
+  if min_x > max_x || min_y > max_y {
+    return stats;
+  }
+
+  // Edge values at the center of the top-left pixel of the bounding box
+  let origin = pixel_center(min_x, min_y);
+  let pattern = sample_pattern(sink.sample_count());
+  let mut edges = [
+    EdgeStepper::new(&b, &c, &origin, pattern),
+    EdgeStepper::new(&c, &a, &origin, pattern),
+    EdgeStepper::new(&a, &b, &origin, pattern),
+  ];
+
+  let (za, zb, zc) = (v1.transformed_position.z, v2.transformed_position.z, v3.transformed_position.z);
+  let area = triangle_area as f32;
+  let row_width = (max_x - min_x + 1) as usize;
+
+  // Lighting is handled inside the procedural shader (planet_shader).
 
-  // Iterate over each pixel in the bounding box
   for y in min_y..=max_y {
-    for x in min_x..=max_x {
-      let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-
-      // Calculate barycentric coordinates
-      let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
-
-      // Check if the point is inside the triangle
-      if w1 >= 0.0 && w1 <= 1.0 && 
-         w2 >= 0.0 && w2 <= 1.0 &&
-         w3 >= 0.0 && w3 <= 1.0 {
-    // Interpolate position and normal in model space for per-fragment shading
-    let interp_pos = v1.position * w1 + v2.position * w2 + v3.position * w3;
-    let mut interp_norm = v1.transformed_normal * w1 + v2.transformed_normal * w2 + v3.transformed_normal * w3;
-    interp_norm = interp_norm.normalize();
-
-    // Compute color using selected procedural shader (returns Vec3 in [0,1])
-    let rgb = crate::shaders::shade(interp_pos, interp_norm);
-
-    // Convert to Color (u8 channels)
-  let cr = (rgb.x * 255.0).clamp(0.0, 255.0) as u8;
-  let cg = (rgb.y * 255.0).clamp(0.0, 255.0) as u8;
-  let cb = (rgb.z * 255.0).clamp(0.0, 255.0) as u8;
-  let lit_color = Color::new(cr, cg, cb);
-
-    // Interpolate depth
-    let depth = a.z * w1 + b.z * w2 + c.z * w3;
-
-    fragments.push(Fragment::new(x as f32, y as f32, lit_color, depth));
+    for offset in (0..row_width).step_by(BLOCK_WIDTH) {
+      let e1 = edges[0].block(offset as i64);
+      let e2 = edges[1].block(offset as i64);
+      let e3 = edges[2].block(offset as i64);
+
+      // Per-lane sample coverage for the whole block; most blocks outside the
+      // triangle stop here
+      let mut coverage = [0u32; BLOCK_WIDTH];
+      for lane in 0..BLOCK_WIDTH.min(row_width - offset) {
+        for sample in 0..pattern.len() {
+          if e1[lane] + edges[0].sample_offsets[sample] >= edges[0].bias &&
+             e2[lane] + edges[1].sample_offsets[sample] >= edges[1].bias &&
+             e3[lane] + edges[2].sample_offsets[sample] >= edges[2].bias {
+            coverage[lane] |= 1 << sample;
+          }
+        }
+      }
+      if coverage.iter().all(|&mask| mask == 0) {
+        continue;
+      }
+
+      for lane in (0..BLOCK_WIDTH).filter(|&lane| coverage[lane] != 0) {
+        let x = min_x + (offset + lane) as i32;
+        stats.fragments += 1;
+        sink.count_fragment(x as usize, y as usize);
+
+        // Barycentric coordinates at one of the pixel's samples
+        let weights = |sample: usize| {
+          let w1 = (e1[lane] + edges[0].sample_offsets[sample]) as f32 / area;
+          let w2 = (e2[lane] + edges[1].sample_offsets[sample]) as f32 / area;
+          let w3 = (e3[lane] + edges[2].sample_offsets[sample]) as f32 / area;
+          (w1, w2, w3)
+        };
+
+        // Interpolate depth per sample and reject occluded samples before shading
+        let mut passed = 0u32;
+        let mut depths = [0.0f32; MAX_SAMPLES];
+        for sample in (0..pattern.len()).filter(|&sample| coverage[lane] & (1 << sample) != 0) {
+          let (w1, w2, w3) = weights(sample);
+          depths[sample] = za * w1 + zb * w2 + zc * w3;
+          if sink.depth_test(x as usize, y as usize, sample, depths[sample]) {
+            passed |= 1 << sample;
+          }
+        }
+        if passed == 0 {
+          continue;
+        }
+
+        // Shade once per pixel, at its center when the triangle covers it and
+        // otherwise at the first covered sample (avoids extrapolating attributes)
+        let center_inside = e1[lane] >= edges[0].bias && e2[lane] >= edges[1].bias && e3[lane] >= edges[2].bias;
+        let (w1, w2, w3) = if center_inside {
+          (e1[lane] as f32 / area, e2[lane] as f32 / area, e3[lane] as f32 / area)
+        } else {
+          weights(coverage[lane].trailing_zeros() as usize)
+        };
+
+        // Interpolate every attribute (model-space position, normal, tex coords, color)
+        // perspective-correctly for per-fragment shading
+        let weights = perspective_weights(v1, v2, v3, (w1, w2, w3));
+        let varyings = Vertex::interpolate(v1, v2, v3, weights);
+
+        // Compute color using the triangle's procedural shader (linear RGB, may exceed 1),
+        // or show the interpolated data itself in a debug view
+        let shaded = match get_debug_view() {
+          DebugView::Off => shade(primitive.material, &varyings, &primitive.sun, primitive.rings.as_ref()),
+          view => ShaderOutput::lit(debug_shade(view, &varyings, weights, primitive.id)),
+        };
+        stats.shader_invocations += 1;
+        sink.count_shader_invocation(x as usize, y as usize);
+        // Gaps in transparent surfaces leave the pixel as it was
+        if shaded.alpha <= 0.0 {
+          continue;
+        }
+
+        let mut fragment = Fragment::new(x as f32, y as f32, shaded.color, 0.0);
+        fragment.emissive = shaded.emissive;
+        fragment.alpha = shaded.alpha;
+        for sample in (0..pattern.len()).filter(|&sample| passed & (1 << sample) != 0) {
+          fragment.depth = depths[sample];
+          sink.write(&fragment, sample);
+        }
       }
     }
+
+    for edge in edges.iter_mut() {
+      edge.row += edge.step_y;
+    }
   }
 
-  fragments
+  stats
+}
+
+// Screen-space barycentrics are affine in x/y, but attributes are only affine in
+// 1/w-scaled space. Weight each vertex by 1/w and renormalize before blending.
+// Depth (NDC z) is already affine in screen space and uses the plain weights.
+fn perspective_weights(v1: &Vertex, v2: &Vertex, v3: &Vertex, (w1, w2, w3): (f32, f32, f32)) -> (f32, f32, f32) {
+  let p1 = w1 / v1.clip_position.w;
+  let p2 = w2 / v2.clip_position.w;
+  let p3 = w3 / v3.clip_position.w;
+  let sum = p1 + p2 + p3;
+
+  (p1 / sum, p2 / sum, p3 / sum)
 }
 
-fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
+pub fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
     let min_x = v1.x.min(v2.x).min(v3.x).floor() as i32;
     let min_y = v1.y.min(v2.y).min(v3.y).floor() as i32;
     let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
@@ -71,16 +360,230 @@ fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i3
     (min_x, min_y, max_x, max_y)
 }
 
-fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) -> (f32, f32, f32) {
-    let w1 = edge_function(b, c, p) / area;
-    let w2 = edge_function(c, a, p) / area;
-    let w3 = edge_function(a, b, p) / area;
-
-    (w1, w2, w3)
-}
-
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
     (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
 }
 
 
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use nalgebra_glm::{self as glm, Vec2, Vec4, Mat3, Mat4};
+  use crate::camera::{Camera, create_viewport_matrix};
+  use crate::shaders::vertex_shader;
+  use crate::Uniforms;
+
+  const WIDTH: f32 = 200.0;
+  const HEIGHT: f32 = 150.0;
+
+  // Records which pixels the rasterizer covered
+  struct Coverage(Vec<(usize, usize)>);
+
+  impl FragmentSink for Coverage {
+    fn sample_count(&self) -> usize {
+      1
+    }
+
+    fn depth_test(&mut self, x: usize, y: usize, _sample: usize, _depth: f32) -> bool {
+      self.0.push((x, y));
+      false
+    }
+
+    fn write(&mut self, _fragment: &Fragment, _sample: usize) {}
+  }
+
+  // Counts how many times each pixel of a small target gets covered
+  struct CoverageCount {
+    width: usize,
+    counts: Vec<u32>,
+  }
+
+  impl FragmentSink for CoverageCount {
+    fn sample_count(&self) -> usize {
+      1
+    }
+
+    fn depth_test(&mut self, x: usize, y: usize, _sample: usize, _depth: f32) -> bool {
+      self.counts[y * self.width + x] += 1;
+      false
+    }
+
+    fn write(&mut self, _fragment: &Fragment, _sample: usize) {}
+  }
+
+  fn screen_vertex(x: f32, y: f32) -> Vertex {
+    Vertex {
+      transformed_position: Vec3::new(x, y, 0.5),
+      ..Vertex::default()
+    }
+  }
+
+  // The quad spans (10.5, 10.5)..(70.5, 50.5): its left/top edges run through pixel
+  // centers (owned) and so do its right/bottom edges (not owned).
+  const QUAD_MIN: (f32, f32) = (10.5, 10.5);
+  const QUAD_MAX: (f32, f32) = (70.5, 50.5);
+
+  fn assert_covered_exactly_once(triangles: &[[Vertex; 3]]) {
+    let (width, height) = (80, 60);
+    let mut coverage = CoverageCount { width, counts: vec![0; width * height] };
+    for [v1, v2, v3] in triangles {
+      triangle(v1, v2, v3, Primitive::default(), (0, 0, width as i32 - 1, height as i32 - 1), &mut coverage);
+    }
+
+    for y in 0..height {
+      for x in 0..width {
+        let inside = (10..70).contains(&x) && (10..50).contains(&y);
+        let expected = if inside { 1 } else { 0 };
+        assert_eq!(coverage.counts[y * width + x], expected, "pixel ({}, {})", x, y);
+      }
+    }
+  }
+
+  #[test]
+  fn tessellated_quad_covers_every_pixel_exactly_once() {
+    let (cols, rows) = (6, 4);
+    let mut grid = Vec::new();
+    for j in 0..=rows {
+      for i in 0..=cols {
+        let mut x = QUAD_MIN.0 + (QUAD_MAX.0 - QUAD_MIN.0) * i as f32 / cols as f32;
+        let mut y = QUAD_MIN.1 + (QUAD_MAX.1 - QUAD_MIN.1) * j as f32 / rows as f32;
+        // Jitter interior vertices by half-pixel steps so plenty of edges pass
+        // exactly through pixel centers, plus a sub-pixel offset on some of them.
+        if i > 0 && i < cols && j > 0 && j < rows {
+          x += ((i * 7 + j * 13) % 9) as f32 * 0.5 - 2.0;
+          y += ((i * 5 + j * 11) % 7) as f32 * 0.5 - 1.5;
+          if (i + j) % 3 == 0 {
+            x += 0.3;
+          }
+        }
+        grid.push(screen_vertex(x, y));
+      }
+    }
+
+    let mut triangles = Vec::new();
+    for j in 0..rows {
+      for i in 0..cols {
+        let p00 = grid[j * (cols + 1) + i].clone();
+        let p10 = grid[j * (cols + 1) + i + 1].clone();
+        let p01 = grid[(j + 1) * (cols + 1) + i].clone();
+        let p11 = grid[(j + 1) * (cols + 1) + i + 1].clone();
+        // Alternate the split diagonal and the winding from cell to cell
+        let cell = if (i + j) % 2 == 0 {
+          [[p00.clone(), p10.clone(), p11.clone()], [p00, p11, p01]]
+        } else {
+          [[p10.clone(), p00.clone(), p01.clone()], [p10, p01, p11]]
+        };
+        triangles.extend(cell);
+      }
+    }
+
+    assert_covered_exactly_once(&triangles);
+  }
+
+  #[test]
+  fn sliver_fan_covers_every_pixel_exactly_once() {
+    // Thin triangles fanning out from the top-left corner to the far edges
+    let corner = screen_vertex(QUAD_MIN.0, QUAD_MIN.1);
+    let mut rim = Vec::new();
+    for k in 0..=40 {
+      let t = k as f32 / 40.0;
+      rim.push(screen_vertex(QUAD_MAX.0, QUAD_MIN.1 + (QUAD_MAX.1 - QUAD_MIN.1) * t));
+    }
+    for k in (0..40).rev() {
+      let t = k as f32 / 40.0;
+      rim.push(screen_vertex(QUAD_MIN.0 + (QUAD_MAX.0 - QUAD_MIN.0) * t, QUAD_MAX.1));
+    }
+
+    let triangles: Vec<[Vertex; 3]> = rim
+      .windows(2)
+      .map(|pair| [corner.clone(), pair[0].clone(), pair[1].clone()])
+      .collect();
+
+    assert_covered_exactly_once(&triangles);
+  }
+
+  #[test]
+  fn degenerate_triangles_produce_no_fragments() {
+    let mut coverage = CoverageCount { width: 20, counts: vec![0; 400] };
+    let collinear = [screen_vertex(2.5, 2.5), screen_vertex(8.5, 8.5), screen_vertex(14.5, 14.5)];
+    let collapsed = [screen_vertex(5.5, 5.5), screen_vertex(5.5, 5.5), screen_vertex(5.5, 5.5)];
+    for [v1, v2, v3] in [collinear, collapsed] {
+      triangle(&v1, &v2, &v3, Primitive::default(), (0, 0, 19, 19), &mut coverage);
+    }
+    assert!(coverage.counts.iter().all(|&count| count == 0));
+  }
+
+  // Quad receding steeply from the camera: uv (u, v) sits at (2u - 1, 2v - 1, 6v)
+  fn quad_point(uv: Vec2) -> Vec3 {
+    Vec3::new(2.0 * uv.x - 1.0, 2.0 * uv.y - 1.0, 6.0 * uv.y)
+  }
+
+  #[test]
+  fn interpolation_matches_analytic_projection_of_a_quad() {
+    let camera = Camera::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0));
+    let uniforms = Uniforms {
+      model_matrix: Mat4::identity(),
+      normal_matrix: Mat3::identity(),
+      view_matrix: camera.view_matrix(),
+      projection_matrix: camera.projection_matrix(WIDTH, HEIGHT),
+      viewport_matrix: create_viewport_matrix(WIDTH, HEIGHT),
+    };
+    let inverse_view_projection = (uniforms.projection_matrix * uniforms.view_matrix).try_inverse().unwrap();
+
+    let normal = Vec3::new(0.0, 6.0, -2.0).normalize();
+    let corners = [Vec2::new(0.0, 0.0), Vec2::new(1.0, 0.0), Vec2::new(1.0, 1.0), Vec2::new(0.0, 1.0)];
+    let quad: Vec<Vertex> = corners
+      .iter()
+      .map(|&uv| vertex_shader(&Vertex::new(quad_point(uv), normal, uv), &uniforms))
+      .collect();
+
+    let mut max_error: f32 = 0.0;
+    let mut max_affine_error: f32 = 0.0;
+    let mut samples = 0;
+    for [i1, i2, i3] in [[0, 1, 2], [0, 2, 3]] {
+      let (v1, v2, v3) = (&quad[i1], &quad[i2], &quad[i3]);
+      let (a, b, c) = (snap(&v1.transformed_position), snap(&v2.transformed_position), snap(&v3.transformed_position));
+      let area = edge_function_fixed(&a, &b, &c) as f32;
+
+      let mut coverage = Coverage(Vec::new());
+      triangle(v1, v2, v3, Primitive::default(), (0, 0, WIDTH as i32 - 1, HEIGHT as i32 - 1), &mut coverage);
+
+      for (x, y) in coverage.0 {
+        let center = pixel_center(x as i32, y as i32);
+        let weights = (
+          edge_function_fixed(&b, &c, &center) as f32 / area,
+          edge_function_fixed(&c, &a, &center) as f32 / area,
+          edge_function_fixed(&a, &b, &center) as f32 / area,
+        );
+        let varyings = Vertex::interpolate(v1, v2, v3, perspective_weights(v1, v2, v3, weights));
+
+        let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+        // Cast the pixel's ray through the scene and intersect it with the quad's
+        // plane (z = 3y + 3) to get the exact surface point under the pixel center.
+        let ndc_x = point.x / WIDTH * 2.0 - 1.0;
+        let ndc_y = 1.0 - point.y / HEIGHT * 2.0;
+        let near = inverse_view_projection * Vec4::new(ndc_x, ndc_y, -1.0, 1.0);
+        let far = inverse_view_projection * Vec4::new(ndc_x, ndc_y, 1.0, 1.0);
+        let origin = near.xyz() / near.w;
+        let direction = far.xyz() / far.w - origin;
+        let t = (3.0 * origin.y + 3.0 - origin.z) / (direction.z - 3.0 * direction.y);
+        let hit = origin + direction * t;
+        let expected_uv = Vec2::new((hit.x + 1.0) / 2.0, (hit.y + 1.0) / 2.0);
+
+        max_error = max_error
+          .max(glm::distance(&varyings.position, &hit))
+          .max(glm::distance(&varyings.tex_coords, &expected_uv));
+
+        let affine_uv = v1.tex_coords * weights.0 + v2.tex_coords * weights.1 + v3.tex_coords * weights.2;
+        max_affine_error = max_affine_error.max(glm::distance(&affine_uv, &expected_uv));
+        samples += 1;
+      }
+    }
+
+    assert!(samples > 1000, "quad covered only {} pixels", samples);
+    assert!(max_error < 1e-3, "perspective-correct error too large: {}", max_error);
+    // Sanity check that the scene actually exercises perspective distortion
+    assert!(max_affine_error > 0.05, "affine interpolation error unexpectedly small: {}", max_affine_error);
+  }
+}
@@ -1,4 +1,4 @@
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use crate::color::Color;
 
 #[derive(Clone, Debug)]
@@ -7,8 +7,11 @@ pub struct Vertex {
   pub normal: Vec3,
   pub tex_coords: Vec2,
   pub color: Color,
+  pub clip_position: Vec4,
   pub transformed_position: Vec3,
   pub transformed_normal: Vec3,
+  // After the model matrix, for shading that needs scene positions (shadows)
+  pub world_position: Vec3,
 }
 
 impl Vertex {
@@ -18,8 +21,38 @@ impl Vertex {
       normal,
       tex_coords,
       color: Color::black(),
+      clip_position: Vec4::new(position.x, position.y, position.z, 1.0),
       transformed_position: position,
       transformed_normal: normal,
+      world_position: position,
+    }
+  }
+
+  // Linear interpolation of every attribute, used when clipping splits an edge
+  pub fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+    Vertex {
+      position: self.position + (other.position - self.position) * t,
+      normal: self.normal + (other.normal - self.normal) * t,
+      tex_coords: self.tex_coords + (other.tex_coords - self.tex_coords) * t,
+      color: self.color.lerp(&other.color, t),
+      clip_position: self.clip_position + (other.clip_position - self.clip_position) * t,
+      transformed_position: self.transformed_position + (other.transformed_position - self.transformed_position) * t,
+      transformed_normal: self.transformed_normal + (other.transformed_normal - self.transformed_normal) * t,
+      world_position: self.world_position + (other.world_position - self.world_position) * t,
+    }
+  }
+
+  // Barycentric blend of every attribute across a triangle, weights summing to 1
+  pub fn interpolate(v1: &Vertex, v2: &Vertex, v3: &Vertex, w: (f32, f32, f32)) -> Vertex {
+    Vertex {
+      position: v1.position * w.0 + v2.position * w.1 + v3.position * w.2,
+      normal: v1.normal * w.0 + v2.normal * w.1 + v3.normal * w.2,
+      tex_coords: v1.tex_coords * w.0 + v2.tex_coords * w.1 + v3.tex_coords * w.2,
+      color: Color::interpolate(&v1.color, &v2.color, &v3.color, w),
+      clip_position: v1.clip_position * w.0 + v2.clip_position * w.1 + v3.clip_position * w.2,
+      transformed_position: v1.transformed_position * w.0 + v2.transformed_position * w.1 + v3.transformed_position * w.2,
+      transformed_normal: v1.transformed_normal * w.0 + v2.transformed_normal * w.1 + v3.transformed_normal * w.2,
+      world_position: v1.world_position * w.0 + v2.world_position * w.1 + v3.world_position * w.2,
     }
   }
 }
@@ -31,8 +64,10 @@ impl Default for Vertex {
       normal: Vec3::new(0.0, 1.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
       color: Color::black(),
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
     }
   }
 }